@@ -1,19 +1,105 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::io;
+use std::io::Write;
+use std::mem;
 use std::path::{Path, PathBuf};
 
 const OUTPUT_FLUSH_THRESHOLD: usize = 2 * 1024 * 1024;
 const MAX_PARSED_LOGLINE_LENGTH: usize = 2 * 1024;
+const ZSTD_LEVEL: i32 = 3;
+const MAX_OPEN_ENCODERS: usize = 32;     // e.g. with a file per PID, the earliest one is finished to open another one
 
 pub trait FileWriter {
-    fn write(&self, filepath: &Path, content: &String, append: bool) -> io::Result<()>;
+    fn write(&self, filepath: &Path, content: &[u8], append: bool) -> io::Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputCompression {
+    None,
+    Gzip,   // .txt.gz
+    Zstd,   // .txt.zst
+}
+
+impl OutputCompression {
+    pub fn parse(value: &str) -> Option<OutputCompression> {
+        match value {
+            "none" => Some(OutputCompression::None),
+            "gzip" | "gz" => Some(OutputCompression::Gzip),
+            "zstd" | "zst" => Some(OutputCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputCompression::None => "none",
+            OutputCompression::Gzip => "gzip",
+            OutputCompression::Zstd => "zstd",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputCompression::None => ".txt",
+            OutputCompression::Gzip => ".txt.gz",
+            OutputCompression::Zstd => ".txt.zst",
+        }
+    }
+}
+
+// the streaming compressor of an output file, kept across the flushes so that the file is one compressed stream
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(compression: OutputCompression) -> io::Result<Option<Encoder>> {
+        match compression {
+            OutputCompression::None => Ok(None),
+            OutputCompression::Gzip => Ok(Some(Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())))),
+            OutputCompression::Zstd => Ok(Some(Encoder::Zstd(zstd::Encoder::new(Vec::new(), ZSTD_LEVEL)?))),
+        }
+    }
+
+    // compress the content, and take the compressed data so far, which is decodable up to the content
+    fn compress(&mut self, content: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(content)?;
+                encoder.flush()?;
+                Ok(mem::take(encoder.get_mut()))
+            },
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(content)?;
+                encoder.flush()?;
+                Ok(mem::take(encoder.get_mut()))
+            },
+        }
+    }
+
+    // the end of the compressed stream
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
 }
 
 pub struct BufferedOutput<'a, T: FileWriter> {
     cache: HashMap<String, String>,
     folder: String,
     active_files: HashSet<String>,
+    append_existing: bool,      // append to the outputs of the previous run instead of truncating them
+    compression: OutputCompression,
+    encoders: HashMap<String, Encoder>,
+    encoder_order: VecDeque<String>,    // the tokens of the open encoders, in the order they were opened
     writer: &'a T,
 }
 
@@ -25,18 +111,30 @@ where T: FileWriter,
             cache: HashMap::new(),
             folder: folder.to_string(),
             active_files: HashSet::new(),
-            writer: &writer,
+            append_existing: false,
+            compression: OutputCompression::None,
+            encoders: HashMap::new(),
+            encoder_order: VecDeque::new(),
+            writer,
         }
     }
 
-    pub fn send(&mut self, token: &str, content: Box<String>) -> io::Result<()> {
+    pub fn set_append_existing(&mut self, append_existing: bool) {
+        self.append_existing = append_existing;
+    }
+
+    pub fn set_compression(&mut self, compression: OutputCompression) {
+        self.compression = compression;
+    }
+
+    pub fn send(&mut self, token: &str, content: &str) -> io::Result<()> {
         if !self.cache.contains_key(token) {
             self.cache.insert(token.to_string(),
              String::with_capacity(OUTPUT_FLUSH_THRESHOLD + MAX_PARSED_LOGLINE_LENGTH));
         }
 
         let cache = self.cache.get_mut(token).unwrap();
-        cache.push_str(&content);
+        cache.push_str(content);
 
         if cache.len() > OUTPUT_FLUSH_THRESHOLD {
             self.do_send(token)?;
@@ -44,14 +142,33 @@ where T: FileWriter,
         Ok(())
     }
 
-    fn do_send(&mut self, token: &str) -> io::Result<()> {
+    fn filepath(&self, token: &str) -> PathBuf {
         let mut filepath = PathBuf::from(&self.folder);
-        filepath.push(token.to_string() + ".txt");
-        let cache = self.cache.get_mut(token).unwrap();
-        let append = self.active_files.contains(token);
+        filepath.push(token.to_string() + self.compression.extension());
+        filepath
+    }
 
-        self.writer.write(&filepath, &cache, append)?;
-        if append == false {
+    fn do_send(&mut self, token: &str) -> io::Result<()> {
+        let filepath = self.filepath(token);
+        let append = self.append_existing || self.active_files.contains(token);
+
+        if !self.encoders.contains_key(token) {
+            if let Some(encoder) = Encoder::new(self.compression)? {
+                // the output goes on as another gzip member or zstd frame when it's sent again
+                if self.encoders.len() == MAX_OPEN_ENCODERS {
+                    let earliest = self.encoder_order.pop_front().unwrap();
+                    self.finish_encoder(&earliest)?;
+                }
+                self.encoders.insert(token.to_string(), encoder);
+                self.encoder_order.push_back(token.to_string());
+            }
+        }
+        let cache = self.cache.get_mut(token).unwrap();
+        match self.encoders.get_mut(token) {
+            Some(encoder) => self.writer.write(&filepath, &encoder.compress(cache.as_bytes())?, append)?,
+            None => self.writer.write(&filepath, cache.as_bytes(), append)?,
+        }
+        if !append {
             self.active_files.insert(token.to_string());
         }
 
@@ -66,6 +183,22 @@ where T: FileWriter,
         }
         Ok(())
     }
+
+    fn finish_encoder(&mut self, token: &str) -> io::Result<()> {
+        if let Some(encoder) = self.encoders.remove(token) {
+            self.writer.write(&self.filepath(token), &encoder.finish()?, true)?;
+        }
+        Ok(())
+    }
+
+    // flush the outputs and end the compressed streams, the outputs shouldn't be sent any more
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        for token in mem::take(&mut self.encoder_order) {
+            self.finish_encoder(&token)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -91,12 +224,12 @@ mod tests {
     }
 
     impl FileWriter for MockFileWriter {
-        fn write(&self, filepath: &Path, content: &String, append: bool) -> io::Result<()> {
+        fn write(&self, filepath: &Path, content: &[u8], append: bool) -> io::Result<()> {
             let filepath = filepath.to_str().unwrap().to_string();
             if self.state.borrow().contains_key(&filepath) {
-                assert!(append == true);
+                assert!(append);
             } else {
-                assert!(append == false);
+                assert!(!append);
                 self.state.borrow_mut().insert(filepath.to_string(), FileState::new());
             }
 
@@ -133,25 +266,105 @@ mod tests {
     #[test]
     fn it_works() {
         const TOKEN: &str = "BAR";
-        const FILEPATH:& str = "FOO\\BAR.txt";
+        let filepath = Path::new("FOO").join("BAR.txt");
+        let filepath = filepath.to_str().unwrap();
         let mock_writer = MockFileWriter::new();
         let mut output = BufferedOutput::new("FOO", &mock_writer);
-        let data = Box::new(String::from_utf8(vec![0u8; 1024]).unwrap());
+        let data = String::from_utf8(vec![0u8; 1024]).unwrap();
 
         for _ in 0..(1024 * 2 - 1) {
-            output.send(TOKEN, data.clone()).unwrap();
+            output.send(TOKEN, &data).unwrap();
         }
-        assert!(mock_writer.file_exists(String::from(FILEPATH)) == false);
+        assert!(!mock_writer.file_exists(String::from(filepath)));
 
         for _ in 0..2 {
-            output.send(TOKEN, data.clone()).unwrap();
+            output.send(TOKEN, &data).unwrap();
         }
-        assert!(mock_writer.get_file_length(String::from(FILEPATH)) == (1024 *2 + 1) * 1024);
-        assert!(mock_writer.get_file_written_times(String::from(FILEPATH)) == 1);
+        assert!(mock_writer.get_file_length(String::from(filepath)) == (1024 *2 + 1) * 1024);
+        assert!(mock_writer.get_file_written_times(String::from(filepath)) == 1);
 
-        output.send(TOKEN, data.clone()).unwrap();
+        output.send(TOKEN, &data).unwrap();
         output.flush().unwrap();
-        assert!(mock_writer.get_file_length(String::from(FILEPATH)) == (1024 *2 + 2) * 1024);
-        assert!(mock_writer.get_file_written_times(String::from(FILEPATH)) == 2);
+        assert!(mock_writer.get_file_length(String::from(filepath)) == (1024 *2 + 2) * 1024);
+        assert!(mock_writer.get_file_written_times(String::from(filepath)) == 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn append_existing() {
+        let filepath = Path::new("FOO").join("BAR.txt");
+        let mock_writer = MockFileWriter::new();
+        mock_writer.state.borrow_mut().insert(filepath.to_str().unwrap().to_string(), FileState::new());
+
+        let mut output = BufferedOutput::new("FOO", &mock_writer);
+        output.set_append_existing(true);
+        output.send("BAR", "LINE\n").unwrap();
+        output.flush().unwrap();    // the mock asserts the appending
+        assert!(mock_writer.get_file_length(filepath.to_str().unwrap().to_string()) == 5);
+    }
+
+    struct DataWriter {
+        files: RefCell<HashMap<PathBuf, Vec<u8>>>,
+    }
+
+    impl FileWriter for DataWriter {
+        fn write(&self, filepath: &Path, content: &[u8], append: bool) -> io::Result<()> {
+            let mut files = self.files.borrow_mut();
+            let data = files.entry(filepath.to_path_buf()).or_default();
+            if !append {
+                data.clear();
+            }
+            data.extend_from_slice(content);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn compression() {
+        use flate2::read::MultiGzDecoder;
+        use std::io::Read;
+
+        for compression in [OutputCompression::Gzip, OutputCompression::Zstd] {
+            let writer = DataWriter { files: RefCell::new(HashMap::new()) };
+            let mut output = BufferedOutput::new("FOO", &writer);
+            output.set_compression(compression);
+            output.send("BAR", "LINE 1\n").unwrap();
+            output.flush().unwrap();
+            output.send("BAR", "LINE 2\n").unwrap();
+            output.finish().unwrap();
+
+            let files = writer.files.borrow();
+            let data = files.get(&Path::new("FOO").join("BAR".to_string() + compression.extension())).unwrap();
+            let mut content = String::new();
+            match compression {
+                OutputCompression::Gzip => MultiGzDecoder::new(data.as_slice()).read_to_string(&mut content).unwrap(),
+                _ => zstd::Decoder::new(data.as_slice()).unwrap().read_to_string(&mut content).unwrap(),
+            };
+            assert!(content == "LINE 1\nLINE 2\n");
+        }
+    }
+
+    #[test]
+    fn open_encoders() {
+        use std::io::Read;
+
+        let writer = DataWriter { files: RefCell::new(HashMap::new()) };
+        let mut output = BufferedOutput::new("FOO", &writer);
+        output.set_compression(OutputCompression::Zstd);
+        for round in 0..2 {
+            for i in 0..MAX_OPEN_ENCODERS + 1 {
+                output.send(&i.to_string(), &format!("LINE {}\n", round)).unwrap();
+                output.flush().unwrap();
+                assert!(output.encoders.len() <= MAX_OPEN_ENCODERS);
+            }
+        }
+        output.finish().unwrap();
+        assert!(output.encoders.is_empty() && output.encoder_order.is_empty());
+
+        let files = writer.files.borrow();
+        for i in 0..MAX_OPEN_ENCODERS + 1 {
+            let mut content = String::new();
+            zstd::Decoder::new(files[&Path::new("FOO").join(format!("{}.txt.zst", i))].as_slice()).unwrap().read_to_string(&mut content).unwrap();
+            assert!(content == "LINE 0\nLINE 1\n");
+        }
+    }
+}
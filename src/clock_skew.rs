@@ -0,0 +1,261 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub const DEFAULT_SKEW_THRESHOLD: i64 = 1000;      // in milliseconds
+const MAX_REPORTED_EVENTS: usize = 100;             // per source, for both clock jumps and backward timestamps
+
+// a period of time in which the offset between the server and local clocks keeps stable
+struct Segment {
+    first_ts: i64,
+    last_ts: i64,
+    offset_sum: i64,
+    count: i64,
+}
+
+impl Segment {
+    fn new(ts: i64, offset: i64) -> Self {
+        Segment { first_ts: ts, last_ts: ts, offset_sum: offset, count: 1 }
+    }
+
+    fn offset(&self) -> i64 {
+        self.offset_sum / self.count
+    }
+}
+
+struct Event {
+    ts: i64,
+    message: String,
+    content: String,
+}
+
+struct SourceSkew {
+    segments: Vec<Segment>,
+    events: Vec<Event>,
+    skipped_events: usize,
+    min_offset: i64,
+    max_offset: i64,
+}
+
+impl SourceSkew {
+    fn new() -> Self {
+        SourceSkew {
+            segments: Vec::new(),
+            events: Vec::new(),
+            skipped_events: 0,
+            min_offset: i64::MAX,
+            max_offset: i64::MIN,
+        }
+    }
+
+    fn add_event(&mut self, ts: i64, message: String, content: &str) {
+        if self.events.len() < MAX_REPORTED_EVENTS {
+            self.events.push(Event { ts, message, content: content.to_string() });
+        } else {
+            self.skipped_events += 1;
+        }
+    }
+}
+
+// the offset is the server timestamp minus the local timestamp of the same record
+// the offsets are followed per process, since the processes of a source may run on different hosts with their own clocks
+pub struct ClockSkew {
+    threshold: i64,
+    tz: Option<FixedOffset>,
+    sources: BTreeMap<(String, String), SourceSkew>,                 // (source, pid) => skew
+    last_ts: HashMap<(String, String), (Option<i64>, Option<i64>)>,  // (source, pid) => (local, server)
+}
+
+impl ClockSkew {
+    pub fn new(threshold: i64) -> Self {
+        ClockSkew {
+            threshold,
+            tz: None,
+            sources: BTreeMap::new(),
+            last_ts: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        self.tz.get_or_insert(line.tz);
+        self.check_backwards(line);
+
+        let (local, server) = match (line.local_ts, line.server_ts) {
+            (Some(local), Some(server)) => (local, server),
+            _ => return,
+        };
+        let offset = server - local;
+        let source = self.sources.entry((line.src.clone(), line.pid.clone())).or_insert_with(SourceSkew::new);
+        source.min_offset = source.min_offset.min(offset);
+        source.max_offset = source.max_offset.max(offset);
+
+        let last_offset = match source.segments.last_mut() {
+            Some(segment) if (offset - segment.offset()).abs() <= self.threshold => {
+                segment.last_ts = segment.last_ts.max(server);
+                segment.offset_sum += offset;
+                segment.count += 1;
+                return;
+            },
+            Some(segment) => Some(segment.offset()),
+            None => None,
+        };
+
+        if let Some(last_offset) = last_offset {
+            let message = format!("offset jumped from {:+} ms to {:+} ms", last_offset, offset);
+            source.add_event(server, message, &line.content);
+        }
+        source.segments.push(Segment::new(server, offset));
+    }
+
+    fn check_backwards(&mut self, line: &LogLine) {
+        let key = (line.src.clone(), line.pid.clone());
+        let (last_local, last_server) = self.last_ts.insert(key, (line.local_ts, line.server_ts)).unwrap_or((None, None));
+
+        let mut messages: Vec<String> = Vec::new();
+        if let (Some(last), Some(ts)) = (last_local, line.local_ts) {
+            if last - ts > self.threshold {
+                messages.push(format!("local timestamp went backwards by {} ms", last - ts));
+            }
+        }
+        if let (Some(last), Some(ts)) = (last_server, line.server_ts) {
+            if last - ts > self.threshold {
+                messages.push(format!("server timestamp went backwards by {} ms", last - ts));
+            }
+        }
+
+        if let Some(ts) = line.timestamp() {
+            for message in messages {
+                let source = self.sources.entry((line.src.clone(), line.pid.clone())).or_insert_with(SourceSkew::new);
+                source.add_event(ts, message, &line.content);
+            }
+        }
+    }
+
+    // the offset of the latest stable period, which could be used to correct the local timestamps
+    pub fn estimate(&self, src: &str, pid: &str) -> Option<i64> {
+        self.sources.get(&(src.to_string(), pid.to_string())).and_then(|source| source.segments.last()).map(|segment| segment.offset())
+    }
+
+    // Example: "... [220101 13:01:02.865] SRC(1:2) ..." => "... [220101 13:01:03.865] SRC(1:2) ..." (with 1 second skew)
+    pub fn correct(&mut self, line: &mut LogLine) {
+        self.add(line);
+        if let (Some(local), Some(offset)) = (line.local_ts, self.estimate(&line.src, &line.pid)) {
+            let from = format!("[{}]", format_timestamp(local, &line.tz));
+            let to = format!("[{}]", format_timestamp(local + offset, &line.tz));
+            if let Some(idx) = line.content[..line.desc_range.start].find(&from) {
+                // the timestamps have the same width, so the description stays in place
+                line.content.replace_range(idx..idx + from.len(), &to);
+            }
+            line.local_ts = Some(local + offset);
+        }
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "offset = server timestamp - local timestamp, threshold: {} ms", self.threshold)?;
+
+        writeln!(w, "\n== clock offset per source and process ==")?;
+        for ((src, pid), source) in self.sources.iter().filter(|(_, source)| !source.segments.is_empty()) {
+            writeln!(w, "{} (pid {}): offset from {:+} ms to {:+} ms, {} stable periods",
+                src, pid, source.min_offset, source.max_offset, source.segments.len())?;
+            for segment in &source.segments {
+                writeln!(w, "    {} - {} {:+} ms ({} records)",
+                    self.format_ts(segment.first_ts), self.format_ts(segment.last_ts), segment.offset(), segment.count)?;
+            }
+        }
+
+        writeln!(w, "\n== clock jumps and backward timestamps ==")?;
+        for ((src, pid), source) in self.sources.iter().filter(|(_, source)| !source.events.is_empty()) {
+            writeln!(w, "{} (pid {}): {} events", src, pid, source.events.len() + source.skipped_events)?;
+            for event in &source.events {
+                writeln!(w, "    {} {}", self.format_ts(event.ts), event.message)?;
+                write!(w, "        {}", event.content)?;
+            }
+            if source.skipped_events > 0 {
+                writeln!(w, "    ... {} more events", source.skipped_events)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn format_ts(&self, ts: i64) -> String {
+        format_timestamp(ts, &self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap()))
+    }
+}
+
+pub fn analyze_folder(folder: &Path, filter: &LogFilter, threshold: i64, pool: &WorkerPool) -> io::Result<()> {
+    let mut skew = ClockSkew::new(threshold);
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            skew.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    skew.report(&mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(local: i64, server: i64, pid: &str) -> LogLine {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let head = format!("DEV_INFO  {} [{}] SRC({}:1) ", format_timestamp(server, &tz), format_timestamp(local, &tz), pid);
+        LogLine::test("DESC").with_local_ts(local).with_server_ts(server).with_pid(pid).with_content(&head, "\n")
+    }
+
+    #[test]
+    fn clock_jumps() {
+        let mut skew = ClockSkew::new(1000);
+        skew.add(&line(1_000_000, 1_000_100, "1"));
+        skew.add(&line(1_001_000, 1_001_300, "1"));
+        skew.add(&line(1_002_000, 1_007_200, "1"));
+        skew.add(&line(1_003_000, 1_008_200, "1"));
+
+        let source = &skew.sources[&("SRC".to_string(), "1".to_string())];
+        assert!(source.segments.len() == 2);
+        assert!(source.segments[0].offset() == 200 && source.segments[0].count == 2);
+        assert!(source.segments[1].offset() == 5200);
+        assert!(source.events.len() == 1);
+        assert!(source.events[0].message == "offset jumped from +200 ms to +5200 ms");
+        assert!(skew.estimate("SRC", "1") == Some(5200));
+
+        // another process of the source, e.g. on another host, has its own clock
+        skew.add(&line(1_004_000, 1_004_100, "2"));
+        assert!(skew.estimate("SRC", "2") == Some(100));
+        assert!(skew.sources[&("SRC".to_string(), "1".to_string())].events.len() == 1);
+    }
+
+    #[test]
+    fn backward_timestamps() {
+        let mut skew = ClockSkew::new(1000);
+        skew.add(&line(1_010_000, 1_010_000, "1"));
+        skew.add(&line(1_000_000, 1_000_000, "2"));
+        skew.add(&line(1_009_500, 1_009_500, "1"));
+        skew.add(&line(1_005_000, 1_005_000, "1"));
+
+        let source = &skew.sources[&("SRC".to_string(), "1".to_string())];
+        assert!(source.events.len() == 2);
+        assert!(source.events[0].message == "local timestamp went backwards by 4500 ms");
+        assert!(source.events[1].message == "server timestamp went backwards by 4500 ms");
+        assert!(skew.sources[&("SRC".to_string(), "2".to_string())].events.is_empty());
+    }
+
+    #[test]
+    fn skew_correction() {
+        let mut skew = ClockSkew::new(1000);
+        let mut l = line(1641013262865, 1641013263865, "1");
+        skew.correct(&mut l);
+        assert!(l.local_ts == Some(1641013263865));
+        assert!(l.content == "DEV_INFO  220101 05:01:03.865 [220101 05:01:03.865] SRC(1:1) DESC\n");
+    }
+}
@@ -0,0 +1,136 @@
+use crate::log_level::LogSeverity;
+use crate::log_parser::LogLine;
+
+use std::env;
+use std::io;
+use std::io::{IsTerminal, Write};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const SOURCE: &str = "\x1b[1;36m";     // bold cyan
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Auto,       // only if stdout is a terminal and NO_COLOR is not set
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Option<ColorMode> {
+        match value {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    pub fn use_color(&self) -> bool {
+        match self {
+            ColorMode::Always => enable_ansi(),
+            ColorMode::Never => false,
+            ColorMode::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() && enable_ansi(),
+        }
+    }
+}
+
+// the legacy Windows console has to be switched to process the escape sequences
+#[cfg(windows)]
+fn enable_ansi() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+fn enable_ansi() -> bool {
+    true
+}
+
+// the level in red or yellow for errors and warnings, the timestamps dimmed and the source highlighted
+// Example: "DEV_ERROR 220101 13:01:02.865 [220101 13:01:02.860] SRC(1:2) DESC [FOO file.cpp 1] [0X2001]"
+pub fn colorize(line: &LogLine) -> String {
+    let content = line.content.as_str();
+    let fields = (|| {
+        let level_end = content.find(' ')?;
+        let ts_start = level_end + content[level_end..].find(|c: char| c != ' ')?;
+        let local_end = ts_start + content[ts_start..].find("] ")? + 1;
+        let src_end = local_end + 1 + content[local_end + 1..].find(' ')?;
+        Some((level_end, ts_start, local_end, src_end))
+    })();
+    let (level_end, ts_start, local_end, src_end) = match fields {
+        Some(fields) => fields,
+        None => return content.to_string(),
+    };
+
+    let mut colored = String::with_capacity(content.len() + 32);
+    match line.level.severity {
+        LogSeverity::Error | LogSeverity::Fatal => colored.push_str(&format!("{}{}{}", RED, &content[..level_end], RESET)),
+        LogSeverity::Warning => colored.push_str(&format!("{}{}{}", YELLOW, &content[..level_end], RESET)),
+        _ => colored.push_str(&content[..level_end]),
+    }
+    colored.push_str(&content[level_end..ts_start]);
+    colored.push_str(&format!("{}{}{} ", DIM, &content[ts_start..local_end], RESET));
+    colored.push_str(&format!("{}{}{}", SOURCE, &content[local_end + 1..src_end], RESET));
+    colored.push_str(&content[src_end..]);
+    colored
+}
+
+// print the parsed lines to stdout (or any other stream), the console counterpart of BufferedOutput
+pub struct ConsoleOutput<W: Write> {
+    w: W,
+    color: bool,
+}
+
+impl<W: Write> ConsoleOutput<W> {
+    pub fn new(w: W, color: bool) -> Self {
+        ConsoleOutput { w, color }
+    }
+
+    pub fn send(&mut self, line: &LogLine) -> io::Result<()> {
+        match self.color {
+            true => self.w.write_all(colorize(line).as_bytes()),
+            false => self.w.write_all(line.content.as_bytes()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the description is "DESC" in the content
+    fn line(level: &str, head: &str, tail: &str) -> LogLine {
+        LogLine::test("DESC").with_level(level).with_content(head, tail)
+    }
+
+    #[test]
+    fn it_works() {
+        let error = line("LOG_DEV_ERROR", "DEV_ERROR 220101 13:01:02.865 [220101 13:01:02.860] SRC(1:2) ", " [FOO file.cpp 1] [0X2001]\n");
+        assert!(colorize(&error) == "\x1b[31mDEV_ERROR\x1b[0m \x1b[2m220101 13:01:02.865 [220101 13:01:02.860]\x1b[0m \
+            \x1b[1;36mSRC(1:2)\x1b[0m DESC [FOO file.cpp 1] [0X2001]\n");
+
+        let info = line("LOG_DEV_INFO", "DEV_INFO  INVALID_TS [220101 13:01:02.860] SRC(1:2) ", " [FOO file.cpp 1] [0X2001]\n");
+        assert!(colorize(&info) == "DEV_INFO  \x1b[2mINVALID_TS [220101 13:01:02.860]\x1b[0m \x1b[1;36mSRC(1:2)\x1b[0m DESC [FOO file.cpp 1] [0X2001]\n");
+
+        // unexpected content is kept as it is
+        let broken = LogLine::test("BROKEN").with_level("LOG_DEV_WARNING");
+        assert!(colorize(&broken) == "BROKEN\n");
+
+        let mut output = ConsoleOutput::new(Vec::new(), false);
+        output.send(&error).unwrap();
+        assert!(output.w == error.content.as_bytes());
+    }
+
+    #[test]
+    fn color_modes() {
+        assert!(ColorMode::parse("always") == Some(ColorMode::Always));
+        assert!(ColorMode::parse("maybe").is_none());
+        assert!(!ColorMode::Never.use_color());
+    }
+}
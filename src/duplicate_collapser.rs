@@ -0,0 +1,129 @@
+use crate::buffered_output::{BufferedOutput, FileWriter};
+use crate::log_parser::{format_timestamp, LogLine};
+
+use std::collections::HashMap;
+use std::io;
+
+struct Run {
+    src: String,
+    desc: String,
+    first_ts: String,
+    last_ts: String,
+    count: usize,
+}
+
+impl Run {
+    fn new(line: &LogLine) -> Self {
+        let ts = Self::format_ts(line);
+        Run {
+            src: line.src.clone(),
+            desc: line.desc().to_string(),
+            first_ts: ts.clone(),
+            last_ts: ts,
+            count: 1,
+        }
+    }
+
+    fn format_ts(line: &LogLine) -> String {
+        match line.timestamp() {
+            Some(ts) => format_timestamp(ts, &line.tz),
+            None => "INVALID_TS".into(),
+        }
+    }
+
+    fn matches(&self, line: &LogLine) -> bool {
+        self.src == line.src && self.desc == line.desc()
+    }
+
+    // Example: "    ... repeated 3 times between 220101 13:01:02.865 and 220101 13:01:03.000"
+    fn marker(&self) -> Option<String> {
+        match self.count {
+            1 => None,
+            _ => Some(format!("    ... repeated {} times between {} and {}\n", self.count, self.first_ts, self.last_ts)),
+        }
+    }
+}
+
+// collapse the runs of records with identical source and description in each split stream,
+// into the first record of the run plus a marker line, one collapser for each kind of streams (source or PID)
+pub struct DuplicateCollapser {
+    runs: HashMap<String, Run>,     // token => the run being collapsed
+}
+
+impl DuplicateCollapser {
+    pub fn new() -> Self {
+        DuplicateCollapser {
+            runs: HashMap::new(),
+        }
+    }
+
+    pub fn send<T: FileWriter>(&mut self, output: &mut BufferedOutput<T>, token: &str, line: &LogLine) -> io::Result<()> {
+        if let Some(run) = self.runs.get_mut(token) {
+            if run.matches(line) {
+                run.count += 1;
+                run.last_ts = Run::format_ts(line);
+                return Ok(());
+            }
+            if let Some(marker) = run.marker() {
+                output.send(token, &marker)?;
+            }
+        }
+
+        self.runs.insert(token.to_string(), Run::new(line));
+        output.send(token, &line.content)
+    }
+
+    pub fn flush<T: FileWriter>(&mut self, output: &mut BufferedOutput<T>) -> io::Result<()> {
+        for (token, run) in self.runs.drain() {
+            if let Some(marker) = run.marker() {
+                output.send(&token, &marker)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use std::cell::RefCell;
+    use std::path::Path;
+
+    struct StringWriter {
+        content: RefCell<String>,
+    }
+
+    impl FileWriter for StringWriter {
+        fn write(&self, _filepath: &Path, content: &[u8], _append: bool) -> io::Result<()> {
+            self.content.borrow_mut().push_str(std::str::from_utf8(content).unwrap());
+            Ok(())
+        }
+    }
+
+    fn line(ts: i64, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_tz(FixedOffset::east_opt(8 * 3600).unwrap())
+    }
+
+    #[test]
+    fn it_works() {
+        let writer = StringWriter { content: RefCell::new(String::new()) };
+        let mut output = BufferedOutput::new("FOO", &writer);
+        let mut collapser = DuplicateCollapser::new();
+
+        collapser.send(&mut output, "SRC", &line(1641013262865, "DESC1")).unwrap();
+        collapser.send(&mut output, "SRC", &line(1641013262900, "DESC1")).unwrap();
+        collapser.send(&mut output, "SRC", &line(1641013263000, "DESC1")).unwrap();
+        collapser.send(&mut output, "SRC", &line(1641013263100, "DESC2")).unwrap();
+        collapser.send(&mut output, "SRC", &line(1641013263200, "DESC1")).unwrap();
+        collapser.send(&mut output, "SRC", &line(1641013263300, "DESC1")).unwrap();
+        collapser.flush(&mut output).unwrap();
+        output.flush().unwrap();
+
+        assert!(*writer.content.borrow() == "DESC1\n\
+            \x20   ... repeated 3 times between 220101 13:01:02.865 and 220101 13:01:03.000\n\
+            DESC2\n\
+            DESC1\n\
+            \x20   ... repeated 2 times between 220101 13:01:03.200 and 220101 13:01:03.300\n");
+    }
+}
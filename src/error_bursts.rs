@@ -0,0 +1,199 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_level::LogSeverity;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::log_template::extract_template;
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub const DEFAULT_BURST_WINDOW: i64 = 1;   // in minutes
+const MINUTE_MS: i64 = 60 * 1000;
+
+// a source is bursting in a time window if its ERROR/WARNING records are BURST_FACTOR times
+// more than its baseline rate, and there are at least MIN_BURST_COUNT of them
+const BURST_FACTOR: f64 = 5.0;
+const MIN_BURST_COUNT: usize = 5;
+const MESSAGE_COUNT: usize = 3;         // representative messages per source in each reported window
+
+struct Message {
+    count: usize,
+    sample: String,
+}
+
+struct SourceWindow {
+    errors: usize,
+    warnings: usize,
+    messages: HashMap<String, Message>,     // template => message
+}
+
+impl SourceWindow {
+    fn issues(&self) -> usize {
+        self.errors + self.warnings
+    }
+}
+
+struct Burst<'a> {
+    window: i64,
+    score: f64,
+    sources: Vec<(&'a String, &'a SourceWindow, f64)>,     // (source, window, baseline)
+}
+
+// highlight the time windows in which the ERROR/WARNING rates of the sources spike
+pub struct ErrorBursts {
+    window: i64,                                            // in milliseconds
+    tz: Option<FixedOffset>,
+    first_window: Option<i64>,
+    last_window: Option<i64>,
+    sources: BTreeMap<String, BTreeMap<i64, SourceWindow>>, // source => window index => window
+}
+
+impl ErrorBursts {
+    pub fn new(window_minutes: i64) -> Self {
+        ErrorBursts {
+            window: window_minutes.max(1) * MINUTE_MS,
+            tz: None,
+            first_window: None,
+            last_window: None,
+            sources: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        let ts = match line.timestamp() {
+            Some(ts) => ts,
+            None => return,
+        };
+        self.tz.get_or_insert(line.tz);
+
+        let window = ts.div_euclid(self.window);
+        self.first_window = Some(self.first_window.map_or(window, |first| first.min(window)));
+        self.last_window = Some(self.last_window.map_or(window, |last| last.max(window)));
+
+        let severity = line.level.severity;
+        if severity < LogSeverity::Warning {
+            return;
+        }
+
+        let windows = self.sources.entry(line.src.clone()).or_default();
+        let stats = windows.entry(window).or_insert_with(|| SourceWindow {
+            errors: 0,
+            warnings: 0,
+            messages: HashMap::new(),
+        });
+        if severity == LogSeverity::Warning {
+            stats.warnings += 1;
+        } else {
+            stats.errors += 1;
+        }
+        stats.messages.entry(extract_template(line.desc())).or_insert_with(|| Message {
+            count: 0,
+            sample: line.content.clone(),
+        }).count += 1;
+    }
+
+    // ranked by the sum of the rates relative to the baselines of the bursting sources
+    fn bursts(&self) -> Vec<Burst<'_>> {
+        let window_count = match (self.first_window, self.last_window) {
+            (Some(first), Some(last)) => (last - first + 1) as f64,
+            _ => return Vec::new(),
+        };
+
+        let mut bursts: BTreeMap<i64, Burst> = BTreeMap::new();
+        for (src, windows) in &self.sources {
+            let baseline = windows.values().map(|stats| stats.issues()).sum::<usize>() as f64 / window_count;
+            for (window, stats) in windows {
+                let issues = stats.issues();
+                if issues < MIN_BURST_COUNT || (issues as f64) < BURST_FACTOR * baseline {
+                    continue;
+                }
+                let burst = bursts.entry(*window).or_insert_with(|| Burst { window: *window, score: 0.0, sources: Vec::new() });
+                burst.score += issues as f64 / baseline;
+                burst.sources.push((src, stats, baseline));
+            }
+        }
+
+        let mut bursts: Vec<Burst> = bursts.into_values().collect();
+        bursts.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap().then(a.window.cmp(&b.window)));
+        for burst in bursts.iter_mut() {
+            burst.sources.sort_by(|a, b| b.1.issues().cmp(&a.1.issues()).then(a.0.cmp(b.0)));
+        }
+        bursts
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W, top: usize) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        let bursts = self.bursts();
+        writeln!(w, "window: {} minutes, {} bursting windows, top {} reported", self.window / MINUTE_MS, bursts.len(), top)?;
+
+        for (rank, burst) in bursts.iter().take(top).enumerate() {
+            let start = format_timestamp(burst.window * self.window, &tz);
+            writeln!(w, "\n#{} {} score {:.1}", rank + 1, &start[..start.len() - 7], burst.score)?;
+            for (src, stats, baseline) in &burst.sources {
+                writeln!(w, "    {}: {} errors, {} warnings (baseline {:.1} per window)", src, stats.errors, stats.warnings, baseline)?;
+
+                let mut messages: Vec<&Message> = stats.messages.values().collect();
+                messages.sort_by(|a, b| b.count.cmp(&a.count).then(a.sample.cmp(&b.sample)));
+                for message in messages.iter().take(MESSAGE_COUNT) {
+                    write!(w, "    {:>8} {}", message.count, message.sample)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn detect_bursts(folder: &Path, filter: &LogFilter, window_minutes: i64, top: usize, pool: &WorkerPool) -> io::Result<()> {
+    let mut bursts = ErrorBursts::new(window_minutes);
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            bursts.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    bursts.report(&mut w, top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_level(level).with_ts(ts).with_src(src)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut bursts = ErrorBursts::new(1);
+        for minute in 0..30 {
+            bursts.add(&line("LOG_DEV_INFO", minute * MINUTE_MS, "SRC1", "heartbeat"));
+            bursts.add(&line("LOG_DEV_WARNING", minute * MINUTE_MS, "SRC1", "slow response"));
+        }
+        for i in 0..10 {
+            bursts.add(&line("LOG_DEV_ERROR", 20 * MINUTE_MS + i, "SRC1", &format!("timeout {}", i)));
+            bursts.add(&line("LOG_SVC_ERROR", 20 * MINUTE_MS + i, "SRC2", "disk full"));
+        }
+        for i in 0..6 {
+            bursts.add(&line("LOG_SVC_ERROR", 5 * MINUTE_MS + i, "SRC2", "disk full"));
+        }
+
+        let ranked = bursts.bursts();
+        assert!(ranked.len() == 2);
+        assert!(ranked[0].window == 20 && ranked[0].sources.len() == 2);
+        assert!(ranked[1].window == 5 && ranked[1].sources.len() == 1);
+
+        let mut report: Vec<u8> = Vec::new();
+        bursts.report(&mut report, 1).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("#1 700101 00:20"));
+        assert!(report.contains("    SRC1: 10 errors, 1 warnings (baseline 1.3 per window)\n          10 timeout 0\n           1 slow response\n"));
+        assert!(!report.contains("#2"));
+    }
+}
@@ -1,20 +1,51 @@
+use crate::console_output::ConsoleOutput;
 use crate::file_system::read_file;
+use crate::line_preparer::LinePreparer;
 use crate::log_parser::LogParser;
+use crate::options::Options;
 
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
-pub fn parse_file(filepath: &Path) -> io::Result<()> {
-    if let Some(content) = read_file(&filepath) {
-        let output = PathBuf::from(filepath.to_str().unwrap().to_string() + ".txt");
+pub const STDIN_PATH: &str = "-";
+
+pub fn parse_file(filepath: &Path, options: &Options) -> io::Result<()> {
+    if let Some(content) = read_file(filepath) {
+        let output = match &options.output {
+            Some(folder) => {
+                fs::create_dir_all(folder)?;
+                folder.join(filepath.file_name().unwrap().to_str().unwrap().to_string() + ".txt")
+            },
+            None => PathBuf::from(filepath.to_str().unwrap().to_string() + ".txt"),
+        };
         let mut f = BufWriter::new(File::create(&output)?);
         let mut parser = LogParser::new();
-        for line in &parser.parse_sync(content) {
-            f.write(&line.content.as_bytes())?;
+        let mut preparer = LinePreparer::new(options);
+        for mut line in parser.parse_sync(content) {
+            if preparer.prepare(&mut line) {
+                f.write_all(line.content.as_bytes())?;
+            }
         }
         f.flush()?;
     }
     Ok(())
 }
+
+// parse a .uihlog stream from stdin as it comes, e.g. "ssh bench cat 1.uihlog | uihlog -", and print the lines to stdout
+pub fn parse_stdin(options: &Options) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut output = ConsoleOutput::new(BufWriter::new(stdout.lock()), options.color.use_color());
+    let mut preparer = LinePreparer::new(options);
+    LogParser::new().parse_stream(stdin.lock(), |lines| {
+        for mut line in lines {
+            if preparer.prepare(&mut line) {
+                output.send(&line)?;
+            }
+        }
+        output.flush()
+    })
+}
@@ -1,35 +1,86 @@
 use crate::buffered_output::FileWriter;
+use crate::log_archive::{entry_size, read_entry, read_entry_with, split_entry_path};
 
 use std::fs;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 pub fn get_file_list(folder: &Path) -> Vec<PathBuf> {
     let mut files: Vec<PathBuf> = Vec::new();
     if let Ok(dir) = fs::read_dir(folder) {
-        for path in dir {
-            if let Ok(path) = path {
-                let path = path.path();
-                if path.is_file() {
-                    files.push(path); 
-                }
+        for path in dir.flatten() {
+            let path = path.path();
+            if path.is_file() {
+                files.push(path);
             }
         }
     }
     files
 }
 
+// the log file could also be an entry inside an archive, see log_archive
+pub fn read_bytes(filepath: &Path) -> io::Result<Vec<u8>> {
+    match split_entry_path(filepath) {
+        Some(_) => read_entry(filepath),
+        None => fs::read(filepath),
+    }
+}
+
+// the size of the file, or of the entry inside an archive, without reading it
+pub fn file_size(filepath: &Path) -> io::Result<u64> {
+    match split_entry_path(filepath) {
+        Some(_) => entry_size(filepath),
+        None => Ok(fs::metadata(filepath)?.len()),
+    }
+}
+
 pub fn read_file(filepath: &Path) -> Option<String> {
-    if let Ok(data) = fs::read(filepath) {
+    if let Ok(data) = read_bytes(filepath) {
         let data = String::from_utf8_lossy(&data);  // consider log file with invalid UTF8 content
-        return Some(data.into_owned())
+        return Some(data.into_owned());
     }
-    println!("failed to read from file {:?}", &filepath);
+    eprintln!("failed to read from file {:?}", &filepath);
     None
 }
 
+// read at most `length` bytes from the beginning of the file
+pub fn read_prefix(filepath: &Path, length: usize) -> io::Result<Vec<u8>> {
+    let read = |r: &mut dyn Read| {
+        let mut data = Vec::with_capacity(length);
+        r.take(length as u64).read_to_end(&mut data)?;
+        Ok(data)
+    };
+    match split_entry_path(filepath) {
+        Some(_) => read_entry_with(filepath, read),
+        None => read(&mut File::open(filepath)?),
+    }
+}
+
+// read the rest of the file after the given offset
+pub fn read_from(filepath: &Path, offset: u64) -> io::Result<Vec<u8>> {
+    read_range(filepath, offset, u64::MAX)
+}
+
+// read the given length of the file from the offset, or less at the end of the file
+// an entry of an archive can't be seeked, so it's read through up to the offset
+pub fn read_range(filepath: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match split_entry_path(filepath) {
+        Some(_) => read_entry_with(filepath, |r| {
+            io::copy(&mut (&mut *r).take(offset), &mut io::sink())?;
+            r.take(length).read_to_end(&mut data)
+        })?,
+        None => {
+            let mut f = File::open(filepath)?;
+            f.seek(SeekFrom::Start(offset))?;
+            f.take(length).read_to_end(&mut data)?
+        },
+    };
+    Ok(data)
+}
+
 pub struct RealFileWriter {
 }
 
@@ -40,23 +91,23 @@ impl RealFileWriter {
 }
 
 impl FileWriter for RealFileWriter {
-    fn write(&self, filepath: &Path, content: &String, append: bool) -> io::Result<()> {
+    fn write(&self, filepath: &Path, content: &[u8], append: bool) -> io::Result<()> {
         let result = match append {
-            true => OpenOptions::new().write(true).append(true).open(filepath),
+            true => OpenOptions::new().append(true).create(true).open(filepath),
             false => OpenOptions::new().write(true).create(true).truncate(true).open(filepath),
         };
 
         match result {
-            Ok(mut f) => match f.write(&content.as_bytes()) {
-                Ok(_) => { return Ok(()); },
+            Ok(mut f) => match f.write_all(content) {
+                Ok(_) => Ok(()),
                 Err(e) => {
-                    println!("failed to write file {:?}: {}", filepath, e.to_string());
-                    return Err(e);
+                    eprintln!("failed to write file {:?}: {}", filepath, e);
+                    Err(e)
                 }
             },
             Err(e) => {
-                println!("failed to open file {:?}: {}", filepath, e.to_string());
-                return Err(e);
+                eprintln!("failed to open file {:?}: {}", filepath, e);
+                Err(e)
             }
         }
     }
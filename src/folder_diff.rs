@@ -0,0 +1,195 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::LogLine;
+use crate::log_template::{extract_template, template_id};
+use crate::worker_pool::WorkerPool;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+// the frequency of a template is its share of all the records of that side, so that folders of different lengths compare,
+// and it's reported as very different if it's FREQUENCY_FACTOR times higher on one side than on the other side,
+// with at least MIN_FREQUENCY_DIFF records on that side
+const FREQUENCY_FACTOR: f64 = 3.0;
+const MIN_FREQUENCY_DIFF: usize = 5;
+
+struct Template {
+    text: String,
+    count: usize,
+    sample: String,     // the first record of the template, with the time relative to the first record of the folder
+}
+
+// the templates per source of one log folder
+pub struct FolderProfile {
+    total: usize,
+    first_ts: Option<i64>,
+    sources: BTreeMap<String, HashMap<String, Template>>,     // source => template id => template
+}
+
+impl FolderProfile {
+    pub fn new() -> Self {
+        FolderProfile {
+            total: 0,
+            first_ts: None,
+            sources: BTreeMap::new(),
+        }
+    }
+
+    // the records are expected to come in time order roughly, so the first one is taken as the beginning
+    pub fn add(&mut self, line: &LogLine) {
+        self.total += 1;
+        let first_ts = *self.first_ts.get_or_insert(line.timestamp().unwrap_or(0));
+
+        let text = extract_template(line.desc());
+        let templates = self.sources.entry(line.src.clone()).or_default();
+        let template = templates.entry(template_id(&text)).or_insert_with(|| {
+            let relative = line.timestamp().map_or(0, |ts| ts - first_ts);
+            Template {
+                text,
+                count: 0,
+                sample: format!("{:+.3}s {}({}) {}", relative as f64 / 1000.0, line.src, line.pid, line.desc()),
+            }
+        });
+        template.count += 1;
+    }
+
+    pub fn load(folder: &Path, filter: &LogFilter, pool: &WorkerPool) -> io::Result<Self> {
+        let mut profile = FolderProfile::new();
+        parse_folder_with(folder, pool, |lines| {
+            for line in lines.iter().filter(|line| filter.accept(line)) {
+                profile.add(line);
+            }
+            Ok(())
+        })?;
+        Ok(profile)
+    }
+}
+
+// Example: 2 of 100 records against 20 of 200 records, the frequency is 2% against 10%
+fn is_different(a: usize, total_a: usize, b: usize, total_b: usize) -> bool {
+    let frequency_a = a as f64 / total_a.max(1) as f64;
+    let frequency_b = b as f64 / total_b.max(1) as f64;
+    if frequency_a > frequency_b {
+        a >= MIN_FREQUENCY_DIFF && frequency_a >= FREQUENCY_FACTOR * frequency_b
+    } else {
+        b >= MIN_FREQUENCY_DIFF && frequency_b >= FREQUENCY_FACTOR * frequency_a
+    }
+}
+
+pub fn report_diff<W: Write>(a: &FolderProfile, b: &FolderProfile, w: &mut W) -> io::Result<()> {
+    writeln!(w, "A: {} records, B: {} records", a.total, b.total)?;
+
+    let empty: HashMap<String, Template> = HashMap::new();
+    let sources: BTreeSet<&String> = a.sources.keys().chain(b.sources.keys()).collect();
+    for src in sources {
+        let templates_a = a.sources.get(src).unwrap_or(&empty);
+        let templates_b = b.sources.get(src).unwrap_or(&empty);
+
+        let mut only_a: Vec<&Template> = templates_a.iter().filter(|(id, _)| !templates_b.contains_key(*id)).map(|(_, t)| t).collect();
+        let mut only_b: Vec<&Template> = templates_b.iter().filter(|(id, _)| !templates_a.contains_key(*id)).map(|(_, t)| t).collect();
+        let mut different: Vec<(&Template, &Template)> = templates_a.iter()
+            .filter_map(|(id, t)| templates_b.get(id).map(|other| (t, other)))
+            .filter(|(t, other)| is_different(t.count, a.total, other.count, b.total))
+            .collect();
+        if only_a.is_empty() && only_b.is_empty() && different.is_empty() {
+            continue;
+        }
+
+        let side = match (templates_a.is_empty(), templates_b.is_empty()) {
+            (false, true) => " (only in A)",
+            (true, false) => " (only in B)",
+            _ => "",
+        };
+        writeln!(w, "\n== {}{} ==", src, side)?;
+
+        only_a.sort_by(|x, y| y.count.cmp(&x.count).then(x.text.cmp(&y.text)));
+        only_b.sort_by(|x, y| y.count.cmp(&x.count).then(x.text.cmp(&y.text)));
+        different.sort_by(|x, y| x.0.text.cmp(&y.0.text));
+        for (title, templates) in [("only in A", &only_a), ("only in B", &only_b)] {
+            if templates.is_empty() {
+                continue;
+            }
+            writeln!(w, "{}:", title)?;
+            for template in templates.iter() {
+                writeln!(w, "{:>8} {}", template.count, template.text)?;
+                writeln!(w, "             {}", template.sample)?;
+            }
+        }
+        if !different.is_empty() {
+            writeln!(w, "frequency differs:")?;
+            for (template_a, template_b) in different {
+                writeln!(w, "{:>8} => {} {}", template_a.count, template_b.count, template_a.text)?;
+                writeln!(w, "             A: {}", template_a.sample)?;
+                writeln!(w, "             B: {}", template_b.sample)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn diff_folders(a: &Path, b: &Path, filter: &LogFilter, pool: &WorkerPool) -> io::Result<()> {
+    let profile_a = FolderProfile::load(a, filter, pool)?;
+    let profile_b = FolderProfile::load(b, filter, pool)?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    writeln!(w, "A: {:?}\nB: {:?}", a, b)?;
+    report_diff(&profile_a, &profile_b, &mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_src(src)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut a = FolderProfile::new();
+        a.add(&line(1000, "SRC1", "scan 1 started"));
+        a.add(&line(2500, "SRC1", "scan 1 done"));
+        for i in 0..2 {
+            a.add(&line(3000, "SRC1", &format!("retry {}", i)));
+        }
+        for i in 0..20 {
+            a.add(&line(4000, "SRC1", &format!("heartbeat {}", i)));
+        }
+
+        let mut b = FolderProfile::new();
+        b.add(&line(5000, "SRC1", "scan 2 started"));
+        b.add(&line(9000, "SRC1", "scan 2 failed"));
+        for i in 0..20 {
+            b.add(&line(9000, "SRC1", &format!("retry {}", i)));
+        }
+        b.add(&line(9000, "SRC2", "crashed"));
+        for i in 0..20 {
+            b.add(&line(10000, "SRC1", &format!("heartbeat {}", i)));
+        }
+
+        let mut report: Vec<u8> = Vec::new();
+        report_diff(&a, &b, &mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("== SRC1 ==\nonly in A:\n       1 scan <NUM> done\n             +1.500s SRC1(1) scan 1 done\n"));
+        assert!(report.contains("only in B:\n       1 scan <NUM> failed\n             +4.000s SRC1(1) scan 2 failed\n"));
+        assert!(report.contains("frequency differs:\n       2 => 20 retry <NUM>\n"));
+        assert!(report.contains("== SRC2 (only in B) ==\nonly in B:\n       1 crashed\n"));
+        assert!(!report.contains("started") && !report.contains("heartbeat"));
+    }
+
+    #[test]
+    fn frequency() {
+        assert!(!is_different(2, 100, 5, 100));
+        assert!(is_different(2, 100, 7, 100));
+        assert!(!is_different(10, 100, 20, 100));
+        assert!(is_different(0, 100, 5, 100));
+
+        // a 2-hour folder against a 10-minute one, with the same share of the records
+        assert!(!is_different(1200, 12000, 100, 1000));
+        assert!(is_different(1200, 12000, 400, 1000));
+        assert!(!is_different(12, 12000, 4, 1000));
+    }
+}
@@ -1,71 +1,169 @@
 use crate::buffered_output::BufferedOutput;
-use crate::file_system::{read_file, RealFileWriter};
+use crate::duplicate_collapser::DuplicateCollapser;
+use crate::file_system::{file_size, read_file, read_from, RealFileWriter};
+use crate::line_preparer::LinePreparer;
 use crate::log_parser::{LogLine, LogParser};
+use crate::options::Options;
+use crate::parse_state::{identify, ParseState};
 use crate::sorted_file_list::SortedFileList;
+use crate::worker_pool::WorkerPool;
 
-use std::collections::VecDeque;
+use std::fs;
 use std::io;
-use std::path::Path;
-use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
-use std::thread;
+use std::path::{Path, PathBuf};
 
-// 2 or 3 is much faster than other configurations if no file writing (on CPU with 4 physical cores)
-const MAX_WORKING_THREADS: usize = 2;
+// the memory reserved for a file being parsed, by its size: the raw data plus the parsed lines, which take about twice as much
+const PARSED_SIZE_FACTOR: usize = 3;
 
-fn create_worker_thread(file_list: &mut SortedFileList) -> Option<Receiver<Vec<LogLine>>> {
-    if let Some(path) = file_list.next() {
-        println!("{:?}", &path.as_path().file_name().unwrap());
-        if let Some(content) = read_file(&path) {
-            let (tx, rx) = mpsc::channel::<Vec<LogLine>>();
-            thread::spawn(move || {
-                let mut parser = LogParser::new();
-                parser.parse_async(content, tx);
-            });
-            return Some(rx);
+struct AppendedFile {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    prefix: Vec<u8>,    // the beginning of the file, for the header and the identity
+    offset: u64,        // where the parsing starts from
+}
+
+// the files to parse with the offsets to start from, everything is parsed again if any parsed file has changed
+fn plan_incremental(folder: &Path, state: &mut ParseState) -> io::Result<Vec<AppendedFile>> {
+    let mut file_list = SortedFileList::new(folder);
+    let mut files: Vec<AppendedFile> = Vec::new();
+    let mut changed = false;
+    while let Some(path) = file_list.next() {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let (size, prefix) = identify(&path)?;
+        let offset = match state.offset(&name, size, &prefix) {
+            Some(offset) if offset == 0 || LogParser::header_length(&prefix).is_some() => offset,
+            _ => {
+                changed = true;
+                0
+            },
+        };
+        files.push(AppendedFile { path, name, size, prefix, offset });
+    }
+
+    if changed {
+        eprintln!("parsed files changed since the last run, parse from scratch");
+        state.clear();
+        for file in files.iter_mut() {
+            file.offset = 0;
         }
     }
-    None
+    files.retain(|file| file.offset < file.size);
+    Ok(files)
 }
 
-pub fn parse_folder(folder: &Path, pid_output: bool) -> io::Result<()> {
-    if pid_output {
-        println!("pid output is enabled");
-    }
+// parse the new files and the appended data since the last run with the worker pool, in file order
+fn parse_appended_with<F>(files: Vec<AppendedFile>, pool: &WorkerPool, state: &mut ParseState, mut handle: F) -> io::Result<()>
+where F: FnMut(Vec<LogLine>) -> io::Result<()>,
+{
+    pool.run(files, |file: AppendedFile, ticket| {
+        eprintln!("{:?}", file.path.as_path().file_name().unwrap());
+        ticket.reserve((file.size - file.offset) as usize * PARSED_SIZE_FACTOR);
+        let data = match read_from(&file.path, file.offset) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("failed to read from file {:?}: {}", &file.path, e);
+                return (file, None);    // parsed again in the next run
+            }
+        };
+        let mut parser = LogParser::new();
+        if file.offset > 0 {
+            parser.parse_appended(&file.prefix[..LogParser::header_length(&file.prefix).unwrap_or(0)], true);
+        }
+        let (lines, consumed) = parser.parse_appended(&data, true);
+        let offset = file.offset + consumed as u64;     // the end of the last complete record
+        (file, Some((lines, offset)))
+    }, |(file, parsed)| {
+        if let Some((lines, offset)) = parsed {
+            handle(lines)?;
+            state.update(&file.name, file.size, &file.prefix, offset);
+        }
+        Ok(())
+    })
+}
 
+// parse all the log files in the folder with the worker pool, and hand over the parsed lines in file order
+pub fn parse_folder_with<F>(folder: &Path, pool: &WorkerPool, handle: F) -> io::Result<()>
+where F: FnMut(Vec<LogLine>) -> io::Result<()>,
+{
     let mut file_list = SortedFileList::new(folder);
-    let mut rxs: VecDeque<Receiver<Vec<LogLine>>> = VecDeque::new();
+    let mut files: Vec<PathBuf> = Vec::with_capacity(file_list.count());
+    while let Some(path) = file_list.next() {
+        files.push(path);
+    }
 
-    let thread_count = std::cmp::min(file_list.count(), MAX_WORKING_THREADS);
-    for _ in 0..thread_count {
-        if let Some(rx) = create_worker_thread(&mut file_list) {
-            rxs.push_back(rx);
-        } else {
-            println!("failed to initialize parsing tasks");
-            break;
+    pool.run(files, |path: PathBuf, ticket| {
+        eprintln!("{:?}", path.as_path().file_name().unwrap());
+        ticket.reserve(file_size(&path).unwrap_or(0) as usize * PARSED_SIZE_FACTOR);
+        match read_file(&path) {
+            Some(content) => LogParser::new().parse_sync(content),
+            None => Vec::new(),
         }
+    }, handle)
+}
+
+pub fn parse_folder(folder: &Path, options: &Options, pool: &WorkerPool) -> io::Result<()> {
+    if options.pid_output {
+        eprintln!("pid output is enabled");
+    }
+    if !options.filter.is_empty() {
+        eprintln!("log filter is enabled");
+    }
+    if options.incremental {
+        eprintln!("incremental parsing is enabled");
+    }
+    if options.redact || !options.redact_patterns.is_empty() {
+        eprintln!("redaction is enabled");
     }
 
+    let output_folder = options.output_folder();
+    fs::create_dir_all(&output_folder)?;
     let writer = RealFileWriter::new();
-    let mut output = BufferedOutput::new(folder.to_str().unwrap(), &writer);
-    loop {
-        if let Some(rx) = rxs.pop_front() {
-            let lines = rx.recv().unwrap();
+    let mut output = BufferedOutput::new(output_folder.to_str().unwrap(), &writer);
+    output.set_compression(options.compression);
+    // a source may be named like a PID, so the two kinds of streams are collapsed apart
+    let mut src_collapser = DuplicateCollapser::new();
+    let mut pid_collapser = DuplicateCollapser::new();
+    let mut preparer = LinePreparer::new(options);
+    let mut state = match options.incremental {
+        true => ParseState::load(&output_folder, &options.output_signature()),
+        false => ParseState::new(&options.output_signature()),
+    };
+    let files = match options.incremental {
+        true => Some(plan_incremental(folder, &mut state)?),
+        false => None,
+    };
+    output.set_append_existing(options.incremental && !state.is_empty());
 
-            if let Some(rx) = create_worker_thread(&mut file_list) {
-                rxs.push_back(rx);
+    let handle = |lines: Vec<LogLine>| {
+        for mut line in lines {
+            if !preparer.prepare(&mut line) {
+                continue;
             }
-
-            for line in lines {
-                if pid_output {
-                    output.send(&line.pid, line.content.clone())?;
+            if options.pid_output {
+                if options.collapse_pid {
+                    pid_collapser.send(&mut output, &line.pid, &line)?;
+                } else {
+                    output.send(&line.pid, &line.content)?;
                 }
-                output.send(&line.src, line.content)?;
             }
-        } else {
-            break;
+            if options.collapse_src {
+                src_collapser.send(&mut output, &line.src, &line)?;
+            } else {
+                output.send(&line.src, &line.content)?;
+            }
         }
+        Ok(())
+    };
+    match files {
+        Some(files) => parse_appended_with(files, pool, &mut state, handle)?,
+        None => parse_folder_with(folder, pool, handle)?,
+    }
+    pid_collapser.flush(&mut output)?;
+    src_collapser.flush(&mut output)?;
+    output.finish()?;
+    if options.incremental {
+        state.save(&output_folder)?;
     }
-    output.flush()?;
     Ok(())
-}
\ No newline at end of file
+}
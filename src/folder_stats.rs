@@ -0,0 +1,185 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_level::LogSeverity;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const MINUTE_MS: i64 = 60 * 1000;
+const HISTOGRAM_WIDTH: usize = 50;
+
+struct SourceStats {
+    count: usize,
+    warnings: usize,
+    errors: usize,
+    first_ts: Option<i64>,
+    last_ts: Option<i64>,
+    pids: BTreeMap<String, usize>,
+    levels: BTreeMap<String, usize>,
+}
+
+impl SourceStats {
+    fn new() -> Self {
+        SourceStats {
+            count: 0,
+            warnings: 0,
+            errors: 0,
+            first_ts: None,
+            last_ts: None,
+            pids: BTreeMap::new(),
+            levels: BTreeMap::new(),
+        }
+    }
+}
+
+pub struct FolderStats {
+    total: usize,
+    tz: Option<FixedOffset>,
+    sources: BTreeMap<String, SourceStats>,
+    minutes: BTreeMap<i64, usize>,          // minute since the Unix epoch => record count
+    descriptions: HashMap<String, usize>,
+}
+
+impl FolderStats {
+    pub fn new() -> Self {
+        FolderStats {
+            total: 0,
+            tz: None,
+            sources: BTreeMap::new(),
+            minutes: BTreeMap::new(),
+            descriptions: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        self.total += 1;
+        self.tz.get_or_insert(line.tz);
+
+        let stats = self.sources.entry(line.src.clone()).or_insert_with(SourceStats::new);
+        stats.count += 1;
+        match line.level.severity {
+            LogSeverity::Warning => stats.warnings += 1,
+            LogSeverity::Error | LogSeverity::Fatal => stats.errors += 1,
+            _ => {},
+        }
+        *stats.pids.entry(line.pid.clone()).or_insert(0) += 1;
+        *stats.levels.entry(line.level.to_string().trim_end().to_string()).or_insert(0) += 1;
+
+        if let Some(ts) = line.timestamp() {
+            stats.first_ts = Some(stats.first_ts.map_or(ts, |first| first.min(ts)));
+            stats.last_ts = Some(stats.last_ts.map_or(ts, |last| last.max(ts)));
+            *self.minutes.entry(ts.div_euclid(MINUTE_MS)).or_insert(0) += 1;
+        }
+
+        if let Some(count) = self.descriptions.get_mut(line.desc()) {
+            *count += 1;
+        } else {
+            self.descriptions.insert(line.desc().to_string(), 1);
+        }
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W, top: usize) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        let format_ts = |ts: Option<i64>| match ts {
+            Some(ts) => format_timestamp(ts, &tz),
+            None => "INVALID_TS".to_string(),
+        };
+
+        writeln!(w, "total records: {}, sources: {}", self.total, self.sources.len())?;
+
+        writeln!(w, "\n== sources ==")?;
+        for (src, stats) in &self.sources {
+            writeln!(w, "{}: {} records, {} warnings, {} errors, from {} to {}",
+                src, stats.count, stats.warnings, stats.errors, format_ts(stats.first_ts), format_ts(stats.last_ts))?;
+            let levels: Vec<String> = stats.levels.iter().map(|(level, count)| format!("{}={}", level, count)).collect();
+            writeln!(w, "    levels: {}", levels.join(", "))?;
+            let pids: Vec<String> = stats.pids.iter().map(|(pid, count)| format!("{}={}", pid, count)).collect();
+            writeln!(w, "    pids: {}", pids.join(", "))?;
+        }
+
+        writeln!(w, "\n== errors per source ==")?;
+        let mut errors: Vec<(&String, &SourceStats)> = self.sources.iter().filter(|(_, stats)| stats.errors > 0).collect();
+        errors.sort_by(|a, b| b.1.errors.cmp(&a.1.errors).then(a.0.cmp(b.0)));
+        for (src, stats) in errors {
+            writeln!(w, "{:>8} {}", stats.errors, src)?;
+        }
+
+        writeln!(w, "\n== top {} descriptions ==", top)?;
+        for (desc, count) in self.top_descriptions(top) {
+            writeln!(w, "{:>8} {}", count, desc)?;
+        }
+
+        writeln!(w, "\n== records per minute ==")?;
+        let max = self.minutes.values().max().cloned().unwrap_or(0);
+        for (minute, count) in &self.minutes {
+            let width = (count * HISTOGRAM_WIDTH + max - 1) / max;     // rounded up
+            let minute = format_timestamp(minute * MINUTE_MS, &tz);
+            writeln!(w, "{} {:>8} {}", &minute[..minute.len() - 7], count, "#".repeat(width))?;
+        }
+        Ok(())
+    }
+
+    // (source, records, warnings, errors) per source
+    pub fn source_counts(&self) -> Vec<(&str, usize, usize, usize)> {
+        self.sources.iter().map(|(src, stats)| (src.as_str(), stats.count, stats.warnings, stats.errors)).collect()
+    }
+
+    fn top_descriptions(&self, top: usize) -> Vec<(&String, usize)> {
+        let mut descriptions: Vec<(&String, usize)> = self.descriptions.iter().map(|(desc, count)| (desc, *count)).collect();
+        descriptions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        descriptions.truncate(top);
+        descriptions
+    }
+}
+
+pub fn stats_folder(folder: &Path, filter: &LogFilter, top: usize, pool: &WorkerPool) -> io::Result<()> {
+    let mut stats = FolderStats::new();
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            stats.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    stats.report(&mut w, top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, ts: i64, src: &str, pid: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_level(level).with_ts(ts).with_tz(FixedOffset::east_opt(8 * 3600).unwrap()).with_src(src).with_pid(pid)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut stats = FolderStats::new();
+        stats.add(&line("LOG_DEV_INFO", 1641013262865, "SRC1", "1", "DESC1"));
+        stats.add(&line("LOG_DEV_ERROR", 1641013263000, "SRC1", "2", "DESC2"));
+        stats.add(&line("LOG_SVC_WARNING", 1641013322865, "SRC2", "3", "DESC1"));
+
+        assert!(stats.total == 3);
+        let src1 = stats.sources.get("SRC1").unwrap();
+        assert!(src1.count == 2 && src1.errors == 1 && src1.warnings == 0);
+        assert!(src1.first_ts == Some(1641013262865) && src1.last_ts == Some(1641013263000));
+        assert!(src1.pids.len() == 2);
+        assert!(src1.levels.get("DEV_ERROR") == Some(&1));
+        assert!(stats.minutes.len() == 2);
+        assert!(stats.top_descriptions(1) == vec![(&"DESC1".to_string(), 2)]);
+        assert!(stats.source_counts() == vec![("SRC1", 2, 0, 1), ("SRC2", 1, 1, 0)]);
+
+        let mut report: Vec<u8> = Vec::new();
+        stats.report(&mut report, 10).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("SRC1: 2 records, 0 warnings, 1 errors, from 220101 13:01:02.865 to 220101 13:01:03.000"));
+        assert!(report.contains("220101 13:01        2 ##"));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub const DEFAULT_GAP_THRESHOLD: i64 = 60 * 1000;     // in milliseconds
+
+// a gap shorter than the threshold is still reported if it's RATE_FACTOR times longer than
+// the average interval of the stream, but it should be at least MIN_RATE_GAP long
+const RATE_FACTOR: i64 = 20;
+const MIN_RATE_GAP: i64 = 10 * 1000;
+
+struct Gap {
+    from: i64,
+    to: i64,
+    before: String,
+    after: String,
+}
+
+struct Stream {
+    count: i64,
+    first_ts: i64,
+    last_ts: i64,
+    last_content: String,
+    candidates: Vec<Gap>,
+}
+
+impl Stream {
+    fn average_interval(&self) -> Option<i64> {
+        match self.count {
+            0 | 1 => None,
+            _ => Some((self.last_ts - self.first_ts) / (self.count - 1)),
+        }
+    }
+}
+
+// detect the periods of silence per source and PID
+pub struct GapDetector {
+    threshold: i64,
+    tz: Option<FixedOffset>,
+    streams: BTreeMap<(String, String), Stream>,    // (source, pid) => stream
+}
+
+impl GapDetector {
+    pub fn new(threshold: i64) -> Self {
+        GapDetector {
+            threshold,
+            tz: None,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        let ts = match line.timestamp() {
+            Some(ts) => ts,
+            None => return,
+        };
+        self.tz.get_or_insert(line.tz);
+
+        let key = (line.src.clone(), line.pid.clone());
+        let min_gap = self.threshold.min(MIN_RATE_GAP);
+        match self.streams.get_mut(&key) {
+            Some(stream) => {
+                if ts - stream.last_ts >= min_gap {
+                    stream.candidates.push(Gap {
+                        from: stream.last_ts,
+                        to: ts,
+                        before: stream.last_content.clone(),
+                        after: line.content.clone(),
+                    });
+                }
+                stream.count += 1;
+                stream.last_ts = stream.last_ts.max(ts);
+                stream.last_content.clone_from(&line.content);
+            },
+            None => {
+                self.streams.insert(key, Stream {
+                    count: 1,
+                    first_ts: ts,
+                    last_ts: ts,
+                    last_content: line.content.clone(),
+                    candidates: Vec::new(),
+                });
+            },
+        }
+    }
+
+    fn gaps<'a>(&self, stream: &'a Stream) -> Vec<&'a Gap> {
+        let rate_gap = stream.average_interval().map_or(self.threshold, |interval| (interval * RATE_FACTOR).max(MIN_RATE_GAP));
+        let min_gap = self.threshold.min(rate_gap);
+        stream.candidates.iter().filter(|gap| gap.to - gap.from >= min_gap).collect()
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        writeln!(w, "threshold: {} ms, or {} times the average interval of the stream (at least {} ms)",
+            self.threshold, RATE_FACTOR, MIN_RATE_GAP)?;
+
+        writeln!(w, "\n== gaps per source and pid ==")?;
+        for ((src, pid), stream) in &self.streams {
+            let gaps = self.gaps(stream);
+            if gaps.is_empty() {
+                continue;
+            }
+            writeln!(w, "{} (pid {}): {} gaps, {} records, average interval {} ms",
+                src, pid, gaps.len(), stream.count, stream.average_interval().unwrap_or(0))?;
+            for gap in gaps {
+                writeln!(w, "    {} ms from {} to {}",
+                    gap.to - gap.from, format_timestamp(gap.from, &tz), format_timestamp(gap.to, &tz))?;
+                write!(w, "        before: {}", gap.before)?;
+                write!(w, "        after:  {}", gap.after)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn detect_gaps(folder: &Path, filter: &LogFilter, threshold: i64, pool: &WorkerPool) -> io::Result<()> {
+    let mut detector = GapDetector::new(threshold);
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            detector.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    detector.report(&mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_src(src)
+    }
+
+    #[test]
+    fn threshold_gaps() {
+        let mut detector = GapDetector::new(60 * 1000);
+        detector.add(&line(0, "SRC1", "FIRST"));
+        detector.add(&line(30 * 1000, "SRC1", "BEFORE"));
+        detector.add(&line(100 * 1000, "SRC2", "OTHER"));
+        detector.add(&line(120 * 1000, "SRC1", "AFTER"));
+
+        let stream = &detector.streams[&("SRC1".to_string(), "1".to_string())];
+        let gaps = detector.gaps(stream);
+        assert!(gaps.len() == 1);
+        assert!(gaps[0].from == 30 * 1000 && gaps[0].to == 120 * 1000);
+        assert!(gaps[0].before == "BEFORE\n" && gaps[0].after == "AFTER\n");
+    }
+
+    #[test]
+    fn rate_gaps() {
+        let mut detector = GapDetector::new(60 * 1000);
+        for i in 0..100 {
+            detector.add(&line(i * 100, "SRC", "BUSY"));
+        }
+        detector.add(&line(99 * 100 + 15 * 1000, "SRC", "AFTER"));
+        let stream = &detector.streams[&("SRC".to_string(), "1".to_string())];
+        assert!(detector.gaps(stream).len() == 1);
+
+        let mut report: Vec<u8> = Vec::new();
+        detector.report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("SRC (pid 1): 1 gaps, 101 records, average interval 249 ms"));
+        assert!(report.contains("    15000 ms from 700101 00:00:09.900 to 700101 00:00:24.900"));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::file_system::read_prefix;
+use crate::folder_parser::parse_folder_with;
+use crate::folder_stats::FolderStats;
+use crate::log_filter::LogFilter;
+use crate::log_level::LogSeverity;
+use crate::log_parser::{LogLine, LogParser};
+use crate::sorted_file_list::SortedFileList;
+use crate::worker_pool::WorkerPool;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const REPORT_FILE: &str = "report.html";
+const MAX_HEADER_LENGTH: usize = 64 * 1024;
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 1em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+td.number { text-align: right; }
+pre, .timeline td, .errors { font-family: monospace; font-size: 12px; }
+.timeline td { border: none; padding: 0 4px; white-space: pre; }
+.timeline tr:target { background: #ffff99; }
+.WARN { color: #b07000; }
+.ERROR, .FATAL { color: #c00000; font-weight: bold; }
+";
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// a single self-contained HTML file: the statistics, the errors, the colour-coded timeline and the file headers
+pub struct HtmlReport {
+    stats: FolderStats,
+    lines: Vec<LogLine>,
+    headers: Vec<(String, String)>,     // (file name, header)
+}
+
+impl HtmlReport {
+    pub fn new() -> Self {
+        HtmlReport {
+            stats: FolderStats::new(),
+            lines: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: LogLine) {
+        self.stats.add(&line);
+        self.lines.push(line);
+    }
+
+    // the control characters are dropped, except the line breaks
+    pub fn add_header(&mut self, name: &str, header: &str) {
+        let header: String = header.chars().filter(|c| !c.is_control() || *c == '\n').collect();
+        self.headers.push((name.to_string(), header));
+    }
+
+    pub fn write<W: Write>(&mut self, w: &mut W, title: &str, top: usize) -> io::Result<()> {
+        self.lines.sort_by_key(|line| line.timestamp().unwrap_or(i64::MIN));    // stable, so the file order is kept for the same timestamp
+        let title = escape_html(title);
+
+        writeln!(w, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>", title, STYLE)?;
+        writeln!(w, "<h1>{}</h1>", title)?;
+        writeln!(w, "<p><a href=\"#sources\">sources</a> | <a href=\"#errors\">errors</a> | <a href=\"#statistics\">statistics</a> | \
+            <a href=\"#timeline\">timeline</a> | <a href=\"#headers\">file headers</a></p>")?;
+
+        writeln!(w, "<h2 id=\"sources\">Sources</h2>\n<table>\n<tr><th>source</th><th>records</th><th>warnings</th><th>errors</th></tr>")?;
+        for (src, count, warnings, errors) in self.stats.source_counts() {
+            writeln!(w, "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                escape_html(src), count, warnings, errors)?;
+        }
+        writeln!(w, "</table>")?;
+
+        let errors: Vec<usize> = (0..self.lines.len()).filter(|i| self.lines[*i].level.severity >= LogSeverity::Error).collect();
+        writeln!(w, "<h2 id=\"errors\">Errors ({})</h2>", errors.len())?;
+        if errors.len() > MAX_REPORTED_ERRORS {
+            writeln!(w, "<p>only the first {} errors are listed</p>", MAX_REPORTED_ERRORS)?;
+        }
+        writeln!(w, "<ol class=\"errors\">")?;
+        for i in errors.iter().take(MAX_REPORTED_ERRORS) {
+            writeln!(w, "<li><a href=\"#r{}\">{}</a></li>", i, escape_html(self.lines[*i].content.trim_end()))?;
+        }
+        writeln!(w, "</ol>")?;
+
+        let mut stats: Vec<u8> = Vec::new();
+        self.stats.report(&mut stats, top)?;
+        writeln!(w, "<h2 id=\"statistics\">Statistics</h2>\n<pre>{}</pre>", escape_html(&String::from_utf8_lossy(&stats)))?;
+
+        writeln!(w, "<h2 id=\"timeline\">Timeline</h2>\n<table class=\"timeline\">")?;
+        for (i, line) in self.lines.iter().enumerate() {
+            writeln!(w, "<tr id=\"r{}\" class=\"{}\"><td>{}</td></tr>", i, line.level.severity.as_str(), escape_html(line.content.trim_end()))?;
+        }
+        writeln!(w, "</table>")?;
+
+        writeln!(w, "<h2 id=\"headers\">File Headers</h2>")?;
+        for (name, header) in &self.headers {
+            writeln!(w, "<h3>{}</h3>\n<pre>{}</pre>", escape_html(name), escape_html(header))?;
+        }
+        writeln!(w, "</body>\n</html>")
+    }
+}
+
+pub fn report_folder(folder: &Path, output: &Path, filter: &LogFilter, top: usize, pool: &WorkerPool) -> io::Result<()> {
+    let mut report = HtmlReport::new();
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.into_iter().filter(|line| filter.accept(line)) {
+            report.add(line);
+        }
+        Ok(())
+    })?;
+
+    let mut file_list = SortedFileList::new(folder);
+    while let Some(path) = file_list.next() {
+        let prefix = read_prefix(&path, MAX_HEADER_LENGTH)?;
+        let header = match LogParser::header_length(&prefix) {
+            Some(length) => String::from_utf8_lossy(&prefix[..length]).into_owned(),
+            None => "INVALID_HEADER".to_string(),
+        };
+        report.add_header(&path.file_name().unwrap().to_string_lossy(), &header);
+    }
+
+    fs::create_dir_all(output)?;
+    let output = output.join(REPORT_FILE);
+    let mut w = BufWriter::new(File::create(&output)?);
+    report.write(&mut w, &format!("uihlog report of {}", folder.display()), top)?;
+    w.flush()?;
+    eprintln!("report written to {:?}", output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_level(level).with_ts(ts).with_src(src).with_content(&format!("{} ", src), "\n")
+    }
+
+    #[test]
+    fn it_works() {
+        let mut report = HtmlReport::new();
+        report.add(line("LOG_DEV_INFO", 2000, "SRC1", "second"));
+        report.add(line("LOG_DEV_ERROR", 3000, "SRC2", "failed <code=5>"));
+        report.add(line("LOG_DEV_WARNING", 1000, "SRC1", "first"));
+        report.add_header("1.uihlog", "version: 1.0\x02\ntimezone: (UTC+08:00)\x03\n");
+
+        let mut html: Vec<u8> = Vec::new();
+        report.write(&mut html, "report of <logs>", 10).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<title>report of &lt;logs&gt;</title>"));
+        assert!(html.contains("<tr><td>SRC1</td><td class=\"number\">2</td><td class=\"number\">1</td><td class=\"number\">0</td></tr>"));
+        assert!(html.contains("<h2 id=\"errors\">Errors (1)</h2>"));
+        assert!(html.contains("<li><a href=\"#r2\">SRC2 failed &lt;code=5&gt;</a></li>"));
+        assert!(html.contains("<tr id=\"r0\" class=\"WARN\"><td>SRC1 first</td></tr>"));
+        assert!(html.contains("<tr id=\"r2\" class=\"ERROR\"><td>SRC2 failed &lt;code=5&gt;</td></tr>"));
+        assert!(html.contains("<h3>1.uihlog</h3>\n<pre>version: 1.0\ntimezone: (UTC+08:00)\n</pre>"));
+        assert!(html.contains("total records: 3, sources: 2"));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::clock_skew::{ClockSkew, DEFAULT_SKEW_THRESHOLD};
+use crate::log_parser::LogLine;
+use crate::log_redactor::LogRedactor;
+use crate::log_template::annotate_line;
+use crate::options::Options;
+
+// apply the output options to the parsed lines, the same way for the split outputs, follow mode and cat
+pub struct LinePreparer<'a> {
+    options: &'a Options,
+    skew: ClockSkew,
+    redactor: LogRedactor,
+}
+
+impl<'a> LinePreparer<'a> {
+    pub fn new(options: &'a Options) -> Self {
+        LinePreparer {
+            options,
+            skew: ClockSkew::new(options.threshold.unwrap_or(DEFAULT_SKEW_THRESHOLD)),
+            redactor: LogRedactor::new(options.redact, &options.redact_patterns),
+        }
+    }
+
+    // false if the line is filtered out
+    pub fn prepare(&mut self, line: &mut LogLine) -> bool {
+        if !self.options.filter.accept(line) {
+            return false;
+        }
+        if self.options.fix_skew {
+            self.skew.correct(line);
+        }
+        if self.options.template_id {
+            annotate_line(line);
+        }
+        self.redactor.redact(line);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Options {
+        Options::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn it_works() {
+        let options = parse(&["--min-level", "warning", "--template-id", "--redact", "logs"]);
+        let mut preparer = LinePreparer::new(&options);
+
+        let mut line = LogLine::test("connected to 10.0.0.1").with_level("LOG_DEV_INFO").with_content("", "\n");
+        assert!(!preparer.prepare(&mut line));
+        let mut line = LogLine::test("connected to 10.0.0.1").with_level("LOG_DEV_WARNING").with_content("", "\n");
+        assert!(preparer.prepare(&mut line));
+        assert!(!line.content.contains("10.0.0.1") && line.content.ends_with("]\n"));
+    }
+}
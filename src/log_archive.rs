@@ -0,0 +1,360 @@
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// the log bundles sent by the field engineers, read without extracting to disk
+// the entries are addressed by the path of the archive joined with their paths inside, e.g. "export.zip/logs/1.uihlog"
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    Gz,     // a single compressed file, e.g. "1.uihlog.gz"
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".gz") {
+        Some(ArchiveKind::Gz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+// a compressed log file in a log folder, parsed along with the plain ones
+pub fn is_compressed_log(path: &Path) -> bool {
+    archive_kind(path) == Some(ArchiveKind::Gz) && path.to_string_lossy().to_ascii_lowercase().ends_with(".uihlog.gz")
+}
+
+// the archive and the path inside, if the path is an entry of an archive file
+pub fn split_entry_path(path: &Path) -> Option<(&Path, &Path)> {
+    let archive = path.ancestors().skip(1).find(|archive| is_archive(archive) && archive.is_file())?;
+    Some((archive, path.strip_prefix(archive).ok()?))
+}
+
+// the name of the single entry of a .gz file, i.e. without the extension
+fn gz_entry_name(archive: &Path) -> PathBuf {
+    PathBuf::from(archive.file_stem().unwrap_or_default())
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("no such entry in the archive: {:?}", path))
+}
+
+// the entries of a zip, opened once so that its central directory isn't read again for each entry
+struct ZipIndex<R> {
+    zip: Mutex<ZipArchive<R>>,
+    entries: Vec<(PathBuf, usize, u64)>,    // path, index in the archive, size
+}
+
+type ZipFileIndex = ZipIndex<BufReader<File>>;
+
+static ZIP_INDEXES: Mutex<Vec<(PathBuf, Arc<ZipFileIndex>)>> = Mutex::new(Vec::new());
+
+fn index_zip<R: Read + Seek>(r: R) -> io::Result<ZipIndex<R>> {
+    let mut zip = ZipArchive::new(r)?;
+    let mut entries = Vec::new();
+    for i in 0..zip.len() {
+        let file = zip.by_index(i)?;
+        if let Some(name) = file.enclosed_name().filter(|_| !file.is_dir()) {
+            entries.push((name.to_path_buf(), i, file.size()));
+        }
+    }
+    Ok(ZipIndex { zip: Mutex::new(zip), entries })
+}
+
+fn find_zip_entry<R>(index: &ZipIndex<R>, entry: &Path) -> io::Result<(usize, u64)> {
+    index.entries.iter().find(|(path, _, _)| path == entry).map(|&(_, i, size)| (i, size)).ok_or_else(|| not_found(entry))
+}
+
+fn read_zip_entry<R: Read + Seek, T, F>(index: &ZipIndex<R>, entry: &Path, read: F) -> io::Result<T>
+where F: FnOnce(&mut dyn Read) -> io::Result<T>,
+{
+    let (i, _) = find_zip_entry(index, entry)?;
+    let mut zip = index.zip.lock().unwrap();
+    let mut file = zip.by_index(i)?;
+    read(&mut file)
+}
+
+fn zip_index(archive: &Path) -> io::Result<Arc<ZipFileIndex>> {
+    let mut indexes = ZIP_INDEXES.lock().unwrap();
+    if let Some((_, index)) = indexes.iter().find(|(path, _)| path == archive) {
+        return Ok(index.clone());
+    }
+    let index = Arc::new(index_zip(BufReader::new(File::open(archive)?))?);
+    indexes.push((archive.to_path_buf(), index.clone()));
+    Ok(index)
+}
+
+// the size modulo 2^32 in the trailer of the (last) gzip member
+fn gz_size<R: Read + Seek>(mut r: R) -> io::Result<u64> {
+    let mut size = [0u8; 4];
+    r.seek(SeekFrom::End(-4))?;
+    r.read_exact(&mut size)?;
+    Ok(u32::from_le_bytes(size) as u64)
+}
+
+// a regular file in a tar, with the position and the size of its data
+struct TarEntry {
+    path: PathBuf,
+    position: u64,
+    size: u64,
+}
+
+// the decompressed stream of a .tar.gz, kept at where the last read stopped
+struct TarStream {
+    decoder: MultiGzDecoder<BufReader<File>>,
+    position: u64,
+}
+
+// the entries of a tar, so that each one is read directly instead of going through the archive again
+// a .tar.gz can't be seeked, so its stream is kept open and only goes back to the beginning for an earlier entry
+struct TarIndex {
+    entries: Vec<TarEntry>,
+    stream: Option<Mutex<Option<TarStream>>>,   // for a .tar.gz
+}
+
+static TAR_INDEXES: Mutex<Vec<(PathBuf, Arc<TarIndex>)>> = Mutex::new(Vec::new());
+
+fn index_tar<R: Read>(r: R) -> io::Result<Vec<TarEntry>> {
+    let mut entries = Vec::new();
+    let mut tar = tar::Archive::new(r);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        // e.g. "./1.uihlog" of a tar made from the current folder
+        let path: PathBuf = entry.path()?.components().filter(|c| *c != Component::CurDir).collect();
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {    // absolute or escaping paths
+            continue;
+        }
+        entries.push(TarEntry { path, position: entry.raw_file_position(), size: entry.size() });
+    }
+    Ok(entries)
+}
+
+fn find_tar_entry<'a>(entries: &'a [TarEntry], entry: &Path) -> io::Result<&'a TarEntry> {
+    entries.iter().find(|e| e.path == entry).ok_or_else(|| not_found(entry))
+}
+
+fn read_tar_entry<R: Read + Seek, T, F>(mut r: R, entries: &[TarEntry], entry: &Path, read: F) -> io::Result<T>
+where F: FnOnce(&mut dyn Read) -> io::Result<T>,
+{
+    let entry = find_tar_entry(entries, entry)?;
+    r.seek(SeekFrom::Start(entry.position))?;
+    read(&mut r.take(entry.size))
+}
+
+fn read_tar_gz_entry<T, F>(archive: &Path, index: &TarIndex, stream: &Mutex<Option<TarStream>>, entry: &Path, read: F) -> io::Result<T>
+where F: FnOnce(&mut dyn Read) -> io::Result<T>,
+{
+    let entry = find_tar_entry(&index.entries, entry)?;
+    let mut stream = stream.lock().unwrap();
+    let mut current = match stream.take() {
+        Some(current) if current.position <= entry.position => current,
+        _ => TarStream { decoder: MultiGzDecoder::new(BufReader::new(File::open(archive)?)), position: 0 },
+    };
+
+    let skipped = io::copy(&mut (&mut current.decoder).take(entry.position - current.position), &mut io::sink())?;
+    if current.position + skipped < entry.position {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("truncated archive: {:?}", archive)));
+    }
+    let mut r = (&mut current.decoder).take(entry.size);
+    let result = read(&mut r);
+    // the stream is kept for the next entries only if it's still in a known state
+    if result.is_ok() {
+        current.position = entry.position + entry.size - r.limit();
+        *stream = Some(current);
+    }
+    result
+}
+
+fn tar_index(archive: &Path, kind: ArchiveKind) -> io::Result<Arc<TarIndex>> {
+    // held while indexing, so that an archive is indexed only once by the worker threads
+    let mut indexes = TAR_INDEXES.lock().unwrap();
+    if let Some((_, index)) = indexes.iter().find(|(path, _)| path == archive) {
+        return Ok(index.clone());
+    }
+
+    let r = BufReader::new(File::open(archive)?);
+    let index = Arc::new(match kind {
+        ArchiveKind::TarGz => TarIndex { entries: index_tar(MultiGzDecoder::new(r))?, stream: Some(Mutex::new(None)) },
+        _ => TarIndex { entries: index_tar(r)?, stream: None },
+    });
+    indexes.push((archive.to_path_buf(), index.clone()));
+    Ok(index)
+}
+
+// the files in the archive, including the ones in the nested folders
+pub fn list_entries(archive: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = match archive_kind(archive) {
+        Some(ArchiveKind::Zip) => zip_index(archive)?.entries.iter().map(|(path, _, _)| path.clone()).collect(),
+        Some(kind @ ArchiveKind::Tar) | Some(kind @ ArchiveKind::TarGz) => {
+            tar_index(archive, kind)?.entries.iter().map(|entry| entry.path.clone()).collect()
+        },
+        Some(ArchiveKind::Gz) => vec![gz_entry_name(archive)],
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not an archive: {:?}", archive))),
+    };
+    Ok(entries.into_iter().map(|entry| archive.join(entry)).collect())
+}
+
+// read the entry given by the path from list_entries as a stream, e.g. to read only its beginning
+pub fn read_entry_with<T, F>(path: &Path, read: F) -> io::Result<T>
+where F: FnOnce(&mut dyn Read) -> io::Result<T>,
+{
+    let (archive, entry) = split_entry_path(path).ok_or_else(|| not_found(path))?;
+    match archive_kind(archive) {
+        Some(ArchiveKind::Zip) => read_zip_entry(zip_index(archive)?.as_ref(), entry, read),
+        Some(kind @ ArchiveKind::Tar) | Some(kind @ ArchiveKind::TarGz) => {
+            let index = tar_index(archive, kind)?;
+            match &index.stream {
+                Some(stream) => read_tar_gz_entry(archive, &index, stream, entry, read),
+                None => read_tar_entry(BufReader::new(File::open(archive)?), &index.entries, entry, read),
+            }
+        },
+        Some(ArchiveKind::Gz) if entry == gz_entry_name(archive) => {
+            read(&mut MultiGzDecoder::new(BufReader::new(File::open(archive)?)))
+        },
+        _ => Err(not_found(path)),
+    }
+}
+
+// the size of the entry given by the path from list_entries, without reading it
+pub fn entry_size(path: &Path) -> io::Result<u64> {
+    let (archive, entry) = split_entry_path(path).ok_or_else(|| not_found(path))?;
+    match archive_kind(archive) {
+        Some(ArchiveKind::Zip) => Ok(find_zip_entry(zip_index(archive)?.as_ref(), entry)?.1),
+        Some(kind @ ArchiveKind::Tar) | Some(kind @ ArchiveKind::TarGz) => Ok(find_tar_entry(&tar_index(archive, kind)?.entries, entry)?.size),
+        Some(ArchiveKind::Gz) if entry == gz_entry_name(archive) => gz_size(File::open(archive)?),
+        _ => Err(not_found(path)),
+    }
+}
+
+// read the whole entry given by the path from list_entries
+pub fn read_entry(path: &Path) -> io::Result<Vec<u8>> {
+    read_entry_with(path, |r| {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        Ok(data)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::env;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::process;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn archive_kinds() {
+        assert!(archive_kind(Path::new("export.ZIP")) == Some(ArchiveKind::Zip));
+        assert!(archive_kind(Path::new("export.tar")) == Some(ArchiveKind::Tar));
+        assert!(archive_kind(Path::new("export.tar.gz")) == Some(ArchiveKind::TarGz));
+        assert!(archive_kind(Path::new("export.tgz")) == Some(ArchiveKind::TarGz));
+        assert!(archive_kind(Path::new("1.uihlog")).is_none());
+        assert!(is_compressed_log(Path::new("logs").join("1.uihlog.gz").as_path()));
+        assert!(!is_compressed_log(Path::new("logs").join("SRC.txt.gz").as_path()));
+        assert!(gz_entry_name(Path::new("1.uihlog.gz")) == Path::new("1.uihlog"));
+    }
+
+    #[test]
+    fn zip_reading() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("logs/", FileOptions::default()).unwrap();
+        zip.start_file("logs/1.uihlog", FileOptions::default()).unwrap();
+        zip.write_all(b"first").unwrap();
+        zip.start_file("logs/nested/2.uihlog", FileOptions::default()).unwrap();
+        zip.write_all(b"second").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        let index = index_zip(Cursor::new(&data)).unwrap();
+        let entries: Vec<PathBuf> = index.entries.iter().map(|(path, _, _)| path.clone()).collect();
+        assert!(entries == vec![Path::new("logs").join("1.uihlog"), Path::new("logs").join("nested").join("2.uihlog")]);
+        assert!(read_zip_entry(&index, &entries[1], read_all).unwrap() == b"second");
+        assert!(read_zip_entry(&index, &entries[0], read_all).unwrap() == b"first");
+        assert!(read_zip_entry(&index, Path::new("3.uihlog"), read_all).is_err());
+        assert!(find_zip_entry(&index, &entries[1]).unwrap().1 == 6);
+    }
+
+    fn read_all(r: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, content.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn gz_sizes() {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&[b'A'; 1000]).unwrap();
+        assert!(gz_size(Cursor::new(gz.finish().unwrap())).unwrap() == 1000);
+    }
+
+    #[test]
+    fn tar_reading() {
+        let data = tar_gz(&[("./logs/1.uihlog", "first"), ("logs/nested/2.uihlog", "second")]);
+        let mut tar = Vec::new();
+        MultiGzDecoder::new(data.as_slice()).read_to_end(&mut tar).unwrap();
+
+        let entries = index_tar(tar.as_slice()).unwrap();
+        assert!(entries.iter().map(|entry| entry.path.clone()).eq([Path::new("logs").join("1.uihlog"), Path::new("logs").join("nested").join("2.uihlog")]));
+        assert!(read_tar_entry(Cursor::new(&tar), &entries, &entries[1].path, read_all).unwrap() == b"second");
+        assert!(read_tar_entry(Cursor::new(&tar), &entries, &entries[0].path, read_all).unwrap() == b"first");
+        assert!(read_tar_entry(Cursor::new(&tar), &entries, Path::new("3.uihlog"), read_all).is_err());
+    }
+
+    #[test]
+    fn tar_gz_file() {
+        let archive = env::temp_dir().join(format!("uihlog_archive_{}.tar.gz", process::id()));
+        fs::write(&archive, tar_gz(&[("1.uihlog", "first"), ("2.uihlog", "second"), ("3.uihlog", "third")])).unwrap();
+
+        let entries = list_entries(&archive).unwrap();
+        assert!(entries == vec![archive.join("1.uihlog"), archive.join("2.uihlog"), archive.join("3.uihlog")]);
+        assert!(entry_size(&entries[1]).unwrap() == 6);
+        let prefix = read_entry_with(&entries[0], |r| {
+            let mut data = Vec::new();
+            r.take(3).read_to_end(&mut data)?;
+            Ok(data)
+        }).unwrap();
+        assert!(prefix == b"fir");
+        // forward from the middle of the first entry, back to the beginning, then forward again
+        assert!(read_entry(&entries[2]).unwrap() == b"third");
+        assert!(read_entry(&entries[0]).unwrap() == b"first");
+        assert!(read_entry(&entries[1]).unwrap() == b"second");
+        assert!(read_entry(&archive.join("4.uihlog")).is_err());
+        fs::remove_file(&archive).unwrap();
+    }
+}
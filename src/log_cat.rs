@@ -0,0 +1,106 @@
+use crate::console_output::ConsoleOutput;
+use crate::file_parser::STDIN_PATH;
+use crate::file_system::read_file;
+use crate::folder_parser::parse_folder_with;
+use crate::line_preparer::LinePreparer;
+use crate::log_archive::is_archive;
+use crate::log_parser::{LogLine, LogParser};
+use crate::options::Options;
+use crate::worker_pool::WorkerPool;
+
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// print the parsed lines as they come in file order, or hold them back to print in timestamp order
+pub struct LogCat<W: Write> {
+    output: ConsoleOutput<W>,
+    merged: Option<Vec<LogLine>>,   // None for the file order
+}
+
+impl<W: Write> LogCat<W> {
+    pub fn new(w: W, color: bool, merge: bool) -> Self {
+        LogCat {
+            output: ConsoleOutput::new(w, color),
+            merged: if merge { Some(Vec::new()) } else { None },
+        }
+    }
+
+    pub fn send(&mut self, line: LogLine) -> io::Result<()> {
+        match &mut self.merged {
+            Some(merged) => {
+                merged.push(line);
+                Ok(())
+            },
+            None => self.output.send(&line),
+        }
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(merged) = &mut self.merged {
+            merged.sort_by_key(|line| line.timestamp().unwrap_or(i64::MIN));    // stable, so the file order is kept for the same timestamp
+            for line in merged.iter() {
+                self.output.send(line)?;
+            }
+            merged.clear();
+        }
+        self.output.flush()
+    }
+}
+
+// write the parsed lines of a log folder (or a single log file, or stdin) to stdout, for the pagers and the shell pipelines
+pub fn cat_folder(path: &Path, options: &Options, pool: &WorkerPool) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut cat = LogCat::new(BufWriter::new(stdout.lock()), options.color.use_color(), options.merge);
+    let mut preparer = LinePreparer::new(options);
+    let mut handle = |lines: Vec<LogLine>| {
+        for mut line in lines {
+            if !preparer.prepare(&mut line) {
+                continue;
+            }
+            cat.send(line)?;
+        }
+        Ok(())
+    };
+
+    if path == Path::new(STDIN_PATH) {
+        LogParser::new().parse_stream(io::stdin().lock(), &mut handle)?;
+    } else if path.is_file() && !is_archive(path) {
+        if let Some(content) = read_file(path) {
+            handle(LogParser::new().parse_sync(content))?;
+        }
+    } else {
+        parse_folder_with(path, pool, &mut handle)?;
+    }
+    cat.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts: i64, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut output: Vec<u8> = Vec::new();
+        let mut cat = LogCat::new(&mut output, false, false);
+        cat.send(line(2000, "second")).unwrap();
+        cat.send(line(1000, "first")).unwrap();
+        cat.finish().unwrap();
+        assert!(output == b"second\nfirst\n");
+    }
+
+    #[test]
+    fn merged_order() {
+        let mut output: Vec<u8> = Vec::new();
+        let mut cat = LogCat::new(&mut output, false, true);
+        cat.send(line(2000, "third")).unwrap();
+        cat.send(line(1000, "first")).unwrap();
+        cat.send(line(1000, "second")).unwrap();
+        cat.finish().unwrap();
+        assert!(output == b"first\nsecond\nthird\n");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::file_system::read_bytes;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{LogParser, FIELD_DELIM, HEADER_END};
+use crate::log_redactor::LogRedactor;
+use crate::log_writer::{LogRecord, LogWriter};
+use crate::sorted_file_list::SortedFileList;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+// write the records of the log file accepted by the filter, under the original header if any is accepted,
+// as they are unless they have to be redacted
+// return the count of the written records
+fn extract_records<W: Write>(data: &[u8], filter: &LogFilter, redactor: &mut LogRedactor, writer: &mut LogWriter<W>) -> io::Result<usize> {
+    let header_length = match LogParser::header_length(data) {
+        Some(length) => length,
+        None => return Ok(0),
+    };
+    let mut parser = LogParser::new();
+    parser.parse_appended(&data[..header_length], true);
+
+    let offsets = LogParser::record_offsets(data);
+    let mut count = 0;
+    for (i, start) in offsets.iter().enumerate() {
+        let raw = &data[*start..offsets.get(i + 1).copied().unwrap_or(data.len())];
+        let (lines, _) = parser.parse_appended(raw, true);
+        let line = match lines.first() {
+            Some(line) if filter.accept(line) => line,
+            _ => continue,
+        };
+        // a record which can't be parsed is redacted field by field, and kept as it is otherwise
+        let record = match redactor.is_enabled() {
+            true => match LogRecord::parse(&String::from_utf8_lossy(raw)) {
+                Some(mut record) => {
+                    redactor.redact_record(&mut record);
+                    Some(Ok(record))
+                },
+                None => {
+                    let fields: Vec<String> = String::from_utf8_lossy(raw).split(FIELD_DELIM).map(|field| redactor.redact_text(field)).collect();
+                    Some(Err(fields.join(&FIELD_DELIM.to_string())))
+                },
+            },
+            false => None,
+        };
+
+        if count == 0 {
+            match redactor.is_enabled() {
+                true => {
+                    let header = redactor.redact_text(&String::from_utf8_lossy(&data[..header_length - HEADER_END.len()]));
+                    writer.write_header(&header, &line.tz)?;
+                },
+                false => writer.write_raw(&data[..header_length])?,
+            }
+        }
+        match record {
+            Some(Ok(record)) => writer.write(&record)?,
+            Some(Err(redacted)) => writer.write_raw(redacted.as_bytes())?,
+            None => writer.write_raw(raw)?,
+        }
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+// write the filtered records of the log folder into a new one, as numbered log files in the original order,
+// which uihlog and the other UIH tools read like the original log folder
+pub fn extract_folder(folder: &Path, output: &Path, filter: &LogFilter, redactor: &mut LogRedactor) -> io::Result<()> {
+    if SortedFileList::new(output).count() > 0 {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("log files exist in the output folder {:?}", output)));
+    }
+    fs::create_dir_all(output)?;
+
+    let mut file_list = SortedFileList::new(folder);
+    let mut files = 0;
+    let mut records = 0;
+    while let Some(path) = file_list.next() {
+        let content = match read_bytes(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("failed to read from file {:?}: {}", &path, e);
+                continue;
+            },
+        };
+        let mut data: Vec<u8> = Vec::new();
+        let count = extract_records(&content, filter, redactor, &mut LogWriter::new(&mut data))?;
+        if count > 0 {
+            files += 1;
+            records += count;
+            fs::write(output.join(format!("{}.uihlog", files)), &data)?;
+        }
+    }
+    eprintln!("{} records extracted into {} files in {:?}", records, files, output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_level::LogSeverity;
+    use crate::log_redactor::DETECTORS;
+    use chrono::FixedOffset;
+    use regex::Regex;
+
+    fn record(ts: i64, level: &str, src: &str, desc: &str) -> LogRecord {
+        LogRecord {
+            level: level.into(),
+            local_ts: ts,
+            src: src.into(),
+            pid: "1001".into(),
+            tid: "7".into(),
+            file: "scan.cpp".into(),
+            line: 42,
+            function: "Scan".into(),
+            uid: "0X2001".into(),
+            desc: desc.into(),
+            server_ts: ts,
+        }
+    }
+
+    fn write_log(records: &[LogRecord]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut writer = LogWriter::new(&mut data);
+        writer.write_header("version 1.0 (UTC+08:00)", &FixedOffset::east_opt(0).unwrap()).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn it_works() {
+        let log = write_log(&[
+            record(1641013262865, "LOG_DEV_INFO", "UI", "started"),
+            record(1641013263865, "LOG_SVC_ERROR", "Recon", "failed"),
+            record(1641013264865, "LOG_DEV_WARNING", "Recon", "retrying"),
+        ]);
+
+        let mut filter = LogFilter::new();
+        filter.add_source("Recon");
+        filter.set_min_severity(LogSeverity::Warning);
+        let mut redactor = LogRedactor::new(false, &[]);
+        let mut data: Vec<u8> = Vec::new();
+        assert!(extract_records(&log, &filter, &mut redactor, &mut LogWriter::new(&mut data)).unwrap() == 2);
+
+        // copied as they are
+        let header_length = LogParser::header_length(&log).unwrap();
+        assert!(data == [&log[..header_length], &log[LogParser::record_offsets(&log)[1]..]].concat());
+        let data = String::from_utf8(data).unwrap();
+        assert!(data.starts_with("version 1.0 (UTC+08:00)\x03\n"));
+        let lines = LogParser::new().parse_sync(data);
+        assert!(lines.len() == 2);
+        assert!(lines[0].tz == FixedOffset::east_opt(8 * 3600).unwrap());
+        assert!(lines[0].desc() == "failed" && lines[1].desc() == "retrying");
+
+        // nothing is written without any accepted record
+        let mut filter = LogFilter::new();
+        filter.add_source("ScanSrv");
+        let mut data: Vec<u8> = Vec::new();
+        assert!(extract_records(&log, &filter, &mut redactor, &mut LogWriter::new(&mut data)).unwrap() == 0);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn redaction() {
+        let mut sensitive = record(1641013262865, "LOG_DEV_INFO", "UI", "sent 1.2.840.10008.1 to 10.0.0.1, DOB: 1980-01-02");
+        sensitive.pid = "10.0.0.2".into();
+        sensitive.file = "C:\\Users\\john\\send.cpp".into();
+        sensitive.function = "Send 00:1A:2b:3C:4d:5E".into();
+        sensitive.uid = "1.2.840.10008.2".into();
+        let mut log = Vec::from(&b"exported from 10.0.0.3 by C:\\Users\\jane\\export.exe "[..]);
+        log.extend_from_slice(&write_log(&[sensitive])[b"version 1.0 ".len()..]);
+
+        let mut redactor = LogRedactor::new(true, &[]);
+        let mut data: Vec<u8> = Vec::new();
+        assert!(extract_records(&log, &LogFilter::new(), &mut redactor, &mut LogWriter::new(&mut data)).unwrap() == 1);
+        let data = String::from_utf8(data).unwrap();
+        for (_, pattern) in DETECTORS.iter() {
+            assert!(!Regex::new(pattern).unwrap().is_match(&data));
+        }
+        let lines = LogParser::new().parse_sync(data);
+        assert!(lines.len() == 1 && lines[0].pid == "<IP_1>" && lines[0].timestamp() == Some(1641013262865));
+    }
+
+    #[test]
+    fn unusual_records() {
+        // the line number isn't a number, and the description goes on after the record terminator
+        let log = String::from_utf8(write_log(&[
+            record(1641013262865, "LOG_DEV_INFO", "UI", "first"),
+            record(1641013263865, "LOG_SVC_ERROR", "Recon", "second"),
+        ])).unwrap();
+        let log = log.replace("scan.cpp\x0242", "scan.cpp\x02N/A").replace("second", "sec\x01\nond");
+
+        let mut redactor = LogRedactor::new(false, &[]);
+        let mut data: Vec<u8> = Vec::new();
+        assert!(extract_records(log.as_bytes(), &LogFilter::new(), &mut redactor, &mut LogWriter::new(&mut data)).unwrap() == 2);
+        assert!(data == log.as_bytes());
+
+        // redacted field by field, since they can't be parsed as records
+        let log = log.replace("first", "first to 10.0.0.1");
+        let mut redactor = LogRedactor::new(true, &[]);
+        let mut data: Vec<u8> = Vec::new();
+        assert!(extract_records(log.as_bytes(), &LogFilter::new(), &mut redactor, &mut LogWriter::new(&mut data)).unwrap() == 2);
+        assert!(data == log.replace("10.0.0.1", "<IP_1>").as_bytes());
+    }
+}
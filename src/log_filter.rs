@@ -0,0 +1,157 @@
+use crate::log_level::{LogCategory, LogSeverity};
+use crate::log_parser::LogLine;
+
+use chrono::{FixedOffset, NaiveDateTime};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+pub struct LogFilter {
+    min_severity: Option<LogSeverity>,
+    categories: HashSet<LogCategory>,
+    sources: BTreeSet<String>,
+    from: Option<NaiveDateTime>,    // inclusive, in the timezone of each log file
+    to: Option<NaiveDateTime>,      // inclusive, in the timezone of each log file
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        LogFilter {
+            min_severity: None,
+            categories: HashSet::new(),
+            sources: BTreeSet::new(),
+            from: None,
+            to: None,
+        }
+    }
+
+    pub fn set_min_severity(&mut self, severity: LogSeverity) {
+        self.min_severity = Some(severity);
+    }
+
+    // could be called multiple times, records of any added category would be accepted
+    pub fn add_category(&mut self, category: LogCategory) {
+        self.categories.insert(category);
+    }
+
+    // could be called multiple times, records of any added source would be accepted
+    pub fn add_source(&mut self, src: &str) {
+        self.sources.insert(src.to_string());
+    }
+
+    pub fn set_from(&mut self, from: NaiveDateTime) {
+        self.from = Some(from);
+    }
+
+    pub fn set_to(&mut self, to: NaiveDateTime) {
+        self.to = Some(to);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_severity.is_none() && self.categories.is_empty() && self.sources.is_empty()
+            && self.from.is_none() && self.to.is_none()
+    }
+
+    pub fn accept_source(&self, src: &str) -> bool {
+        self.sources.is_empty() || self.sources.contains(src)
+    }
+
+    // in milliseconds since the Unix epoch, for the log files of the given timezone
+    pub fn time_range(&self, tz: &FixedOffset) -> (i64, i64) {
+        let to_ms = |time: &NaiveDateTime| time.and_utc().timestamp_millis() - tz.local_minus_utc() as i64 * 1000;
+        (self.from.as_ref().map_or(i64::MIN, to_ms), self.to.as_ref().map_or(i64::MAX, to_ms))
+    }
+
+    pub fn accept(&self, line: &LogLine) -> bool {
+        if let Some(min_severity) = self.min_severity {
+            if line.level.severity < min_severity {
+                return false;
+            }
+        }
+        if !self.accept_source(&line.src) {
+            return false;
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let (from, to) = self.time_range(&line.tz);
+            match line.timestamp() {
+                Some(ts) if ts >= from && ts <= to => (),
+                _ => return false,
+            }
+        }
+        self.categories.is_empty() || self.categories.contains(&line.level.category)
+    }
+}
+
+// Example: "min-level=WARN categories=DEV,SVC sources=UI from=220101 13:00:00.000 to="
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut categories: Vec<&str> = self.categories.iter().map(|category| category.as_str()).collect();
+        categories.sort_unstable();
+        let sources: Vec<&str> = self.sources.iter().map(|src| src.as_str()).collect();
+        let severity = self.min_severity.map_or("", |severity| severity.as_str());
+        let format_time = |time: &Option<NaiveDateTime>| time.map_or(String::new(), |time| time.format("%y%m%d %H:%M:%S%.3f").to_string());
+        write!(f, "min-level={} categories={} sources={} from={} to={}",
+            severity, categories.join(","), sources.join(","), format_time(&self.from), format_time(&self.to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_with_level(level: &str) -> LogLine {
+        LogLine::test("").with_level(level)
+    }
+
+    #[test]
+    fn empty_filter() {
+        let filter = LogFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.accept(&line_with_level("LOG_DEV_INFO")));
+        assert!(filter.accept(&line_with_level("BAD_LEVEL")));
+        assert!(filter.to_string() == "min-level= categories= sources= from= to=");
+    }
+
+    #[test]
+    fn min_severity() {
+        let mut filter = LogFilter::new();
+        filter.set_min_severity(LogSeverity::Warning);
+        assert!(!filter.accept(&line_with_level("LOG_DEV_INFO")));
+        assert!(filter.accept(&line_with_level("LOG_DEV_WARNING")));
+        assert!(filter.accept(&line_with_level("LOG_TRACE_ERROR")));
+        assert!(!filter.accept(&line_with_level("BAD_LEVEL")));
+    }
+
+    #[test]
+    fn categories() {
+        let mut filter = LogFilter::new();
+        filter.add_category(LogCategory::Svc);
+        filter.add_category(LogCategory::Trace);
+        assert!(!filter.accept(&line_with_level("LOG_DEV_ERROR")));
+        assert!(filter.accept(&line_with_level("LOG_SVC_INFO")));
+        assert!(filter.accept(&line_with_level("LOG_TRACE_INFO")));
+
+        filter.set_min_severity(LogSeverity::Warning);
+        assert!(filter.to_string() == "min-level=WARN categories=SVC,TRACE sources= from= to=");
+    }
+
+    #[test]
+    fn sources_and_time_range() {
+        let mut l = line_with_level("LOG_DEV_INFO");
+        l.tz = FixedOffset::east_opt(8 * 3600).unwrap();
+        l.server_ts = Some(1641013262865);     // 220101 13:01:02.865 in UTC+08:00
+
+        let mut filter = LogFilter::new();
+        filter.add_source("SRC");
+        filter.set_from(NaiveDateTime::parse_from_str("220101 13:01:00", "%y%m%d %H:%M:%S").unwrap());
+        assert!(filter.accept(&l));
+        assert!(!filter.accept(&line_with_level("LOG_DEV_INFO")));     // without timestamp
+
+        filter.set_to(NaiveDateTime::parse_from_str("220101 13:01:02", "%y%m%d %H:%M:%S").unwrap());
+        assert!(!filter.accept(&l));
+        assert!(filter.time_range(&l.tz) == (1641013260000, 1641013262000));
+        assert!(filter.to_string() == "min-level= categories= sources=SRC from=220101 13:01:00.000 to=220101 13:01:02.000");
+
+        filter.add_source("OTHER");
+        assert!(filter.accept_source("OTHER") && !filter.accept_source("SRC2"));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::buffered_output::BufferedOutput;
+use crate::console_output::ConsoleOutput;
+use crate::file_system::RealFileWriter;
+use crate::line_preparer::LinePreparer;
+use crate::log_parser::{LogLine, LogParser};
+use crate::options::Options;
+use crate::sorted_file_list::SortedFileList;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const QUIET_POLLS: usize = 4;   // the last record is taken as complete once the file hasn't grown for this many polls
+
+struct FollowedFile {
+    path: PathBuf,
+    offset: u64,            // the length of the file already read
+    pending: Vec<u8>,       // the incomplete records read from the file
+    parser: LogParser,
+    quiet_polls: usize,     // since the file last grew
+}
+
+impl FollowedFile {
+    fn new(path: PathBuf) -> Self {
+        eprintln!("{:?}", path.as_path().file_name().unwrap());
+        FollowedFile {
+            path,
+            offset: 0,
+            pending: Vec::new(),
+            parser: LogParser::new(),
+            quiet_polls: 0,
+        }
+    }
+
+    // parse the complete records appended since the last read
+    fn read(&mut self) -> io::Result<Vec<LogLine>> {
+        let mut f = File::open(&self.path)?;
+        let length = f.metadata()?.len();
+        if length < self.offset {
+            eprintln!("file truncated, read from the beginning again: {:?}", &self.path);
+            self.offset = 0;
+            self.pending.clear();
+            self.parser = LogParser::new();
+        }
+        if length == self.offset {
+            // the logging has stopped for a while, so the last record isn't going on
+            self.quiet_polls += 1;
+            if self.quiet_polls == QUIET_POLLS {
+                return Ok(self.flush());
+            }
+            return Ok(Vec::new());
+        }
+
+        self.quiet_polls = 0;
+        f.seek(SeekFrom::Start(self.offset))?;
+        self.offset += f.read_to_end(&mut self.pending)? as u64;
+        let (lines, consumed) = self.parser.parse_appended(&self.pending, false);
+        self.pending.drain(..consumed);
+        Ok(lines)
+    }
+
+    // parse the last record if it's complete, though it isn't followed by another one yet
+    fn flush(&mut self) -> Vec<LogLine> {
+        let (lines, consumed) = self.parser.parse_appended(&self.pending, true);
+        self.pending.drain(..consumed);
+        lines
+    }
+
+    // parse the last record once the file is complete, it isn't followed by another one
+    fn finish(&mut self) -> Vec<LogLine> {
+        let lines = self.flush();
+        self.pending.clear();
+        lines
+    }
+}
+
+// keep watching the newest log file in the folder, and switch to the new ones when they appear
+pub struct LogFollower {
+    folder: PathBuf,
+    current: Option<FollowedFile>,
+}
+
+impl LogFollower {
+    pub fn new(folder: &Path) -> Self {
+        LogFollower {
+            folder: folder.to_path_buf(),
+            current: None,
+        }
+    }
+
+    // the newest file is parsed from the beginning, since its header is required anyway
+    pub fn poll(&mut self) -> io::Result<Vec<LogLine>> {
+        let mut file_list = SortedFileList::new(&self.folder);
+        let mut files: Vec<PathBuf> = Vec::with_capacity(file_list.count());
+        while let Some(file) = file_list.next() {
+            files.push(file);
+        }
+
+        let mut lines = Vec::new();
+        let newer = match &mut self.current {
+            Some(current) => {
+                let deleted = match current.read() {
+                    Ok(mut read) => {
+                        lines.append(&mut read);
+                        false
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => true,  // deleted or rotated away, nothing more to read
+                    Err(e) => return Err(e),
+                };
+                let newer = match files.iter().position(|file| *file == current.path) {
+                    Some(idx) => files.split_off(idx + 1),
+                    None => files.split_off(files.len().saturating_sub(1)),     // deleted, go on with the newest one
+                };
+                // the current file is complete once it's deleted or a newer one appears
+                if deleted || !newer.is_empty() {
+                    lines.append(&mut current.finish());
+                }
+                newer
+            },
+            None => files.split_off(files.len().saturating_sub(1)),
+        };
+
+        let count = newer.len();
+        for (i, file) in newer.into_iter().enumerate() {
+            let mut followed = FollowedFile::new(file);
+            lines.append(&mut followed.read()?);
+            if i + 1 < count {
+                lines.append(&mut followed.finish());
+            }
+            self.current = Some(followed);
+        }
+        Ok(lines)
+    }
+}
+
+// stream the records to stdout, or to the split outputs, as they are written into the log folder
+pub fn follow_folder(folder: &Path, options: &Options) -> io::Result<()> {
+    eprintln!("following {:?}, press Ctrl+C to stop", folder);
+
+    let output_folder = options.output_folder();
+    if options.split_output {
+        fs::create_dir_all(&output_folder)?;
+    }
+    let writer = RealFileWriter::new();
+    let mut output = BufferedOutput::new(output_folder.to_str().unwrap(), &writer);
+    let mut preparer = LinePreparer::new(options);
+    let mut follower = LogFollower::new(folder);
+    let color = options.color.use_color();
+    loop {
+        let lines = follower.poll()?;
+        if lines.is_empty() {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let stdout = io::stdout();
+        let mut console = ConsoleOutput::new(stdout.lock(), color);
+        for mut line in lines {
+            if !preparer.prepare(&mut line) {
+                continue;
+            }
+            if !options.split_output {
+                console.send(&line)?;
+                continue;
+            }
+            if options.pid_output {
+                output.send(&line.pid, &line.content)?;
+            }
+            output.send(&line.src, &line.content)?;
+        }
+        console.flush()?;
+        output.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_writer::{LogRecord, LogWriter};
+    use chrono::FixedOffset;
+    use std::process;
+
+    fn write_log(path: &Path, descs: &[&str]) {
+        let mut data: Vec<u8> = Vec::new();
+        let mut writer = LogWriter::new(&mut data);
+        writer.write_header("follow test", &FixedOffset::east_opt(0).unwrap()).unwrap();
+        for (i, desc) in descs.iter().enumerate() {
+            writer.write(&LogRecord {
+                level: "LOG_DEV_INFO".into(),
+                local_ts: 1641013262865 + i as i64,
+                src: "SRC".into(),
+                pid: "1".into(),
+                tid: "2".into(),
+                file: "follow.cpp".into(),
+                line: 1,
+                function: "Follow".into(),
+                uid: "0X2001".into(),
+                desc: desc.to_string(),
+                server_ts: 1641013262865 + i as i64,
+            }).unwrap();
+        }
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn deleted_file() {
+        let folder = std::env::temp_dir().join(format!("uihlog_follow_{}", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        write_log(&folder.join("1.uihlog"), &["FIRST"]);
+
+        let mut follower = LogFollower::new(&folder);
+        assert!(follower.poll().unwrap().is_empty());     // the last record may still go on
+
+        // rotated away between two polls
+        fs::remove_file(folder.join("1.uihlog")).unwrap();
+        let lines = follower.poll().unwrap();
+        assert!(lines.len() == 1 && lines[0].desc() == "FIRST");
+        write_log(&folder.join("2.uihlog"), &["SECOND", "THIRD"]);
+        let lines = follower.poll().unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(lines.len() == 1 && lines[0].desc() == "SECOND");
+    }
+
+    #[test]
+    fn quiet_file() {
+        let folder = std::env::temp_dir().join(format!("uihlog_follow_quiet_{}", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        write_log(&folder.join("1.uihlog"), &["FIRST", "SECOND"]);
+
+        let mut follower = LogFollower::new(&folder);
+        let mut descs: Vec<Vec<String>> = Vec::new();
+        let mut poll = |follower: &mut LogFollower| {
+            descs.push(follower.poll().unwrap().iter().map(|line| line.desc().to_string()).collect());
+        };
+        for _ in 0..QUIET_POLLS + 1 {
+            poll(&mut follower);
+        }
+        // appended after the flush
+        write_log(&folder.join("1.uihlog"), &["FIRST", "SECOND", "THIRD"]);
+        for _ in 0..QUIET_POLLS + 2 {
+            poll(&mut follower);
+        }
+        fs::remove_dir_all(&folder).unwrap();
+
+        let mut expected = vec![Vec::new(); QUIET_POLLS * 2 + 3];
+        expected[0] = vec!["FIRST".to_string()];
+        expected[QUIET_POLLS] = vec!["SECOND".to_string()];
+        expected[QUIET_POLLS * 2 + 1] = vec!["THIRD".to_string()];
+        assert!(descs == expected);
+    }
+}
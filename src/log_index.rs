@@ -0,0 +1,392 @@
+use crate::console_output::{ColorMode, ConsoleOutput};
+use crate::file_system::{read_bytes, read_range};
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, LogLine, LogParser};
+use crate::parse_state::{hash_prefix, identify};
+use crate::sorted_file_list::SortedFileList;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = ".uihlog_index";
+const BLOCK_RECORDS: usize = 1000;      // records between two indexed offsets
+
+// a run of records starting from the offset, with the range of their timestamps
+struct Block {
+    offset: u64,
+    min_ts: i64,    // i64::MAX if no record in the block has a valid timestamp
+    max_ts: i64,    // i64::MIN if no record in the block has a valid timestamp
+}
+
+struct FileIndex {
+    size: u64,
+    hash: u64,      // of the beginning of the file, to tell a replaced file of the same size
+    header_length: u64,
+    tz: FixedOffset,
+    records: usize,
+    sources: BTreeSet<String>,
+    pids: BTreeSet<String>,
+    blocks: Vec<Block>,
+}
+
+impl FileIndex {
+    fn build(data: &[u8]) -> Self {
+        let header_length = LogParser::header_length(data).unwrap_or(data.len());
+        let mut index = FileIndex {
+            size: data.len() as u64,
+            hash: hash_prefix(data),
+            header_length: header_length as u64,
+            tz: FixedOffset::east_opt(0).unwrap(),
+            records: 0,
+            sources: BTreeSet::new(),
+            pids: BTreeSet::new(),
+            blocks: Vec::new(),
+        };
+
+        let mut parser = LogParser::new();
+        parser.parse_appended(&data[..header_length], true);
+        let offsets = LogParser::record_offsets(data);
+        for (i, start) in offsets.iter().enumerate().step_by(BLOCK_RECORDS) {
+            let end = offsets.get(i + BLOCK_RECORDS).copied().unwrap_or(data.len());
+            let (lines, _) = parser.parse_appended(&data[*start..end], true);
+
+            let mut block = Block { offset: *start as u64, min_ts: i64::MAX, max_ts: i64::MIN };
+            for line in &lines {
+                index.tz = line.tz;
+                if let Some(ts) = line.timestamp() {
+                    block.min_ts = block.min_ts.min(ts);
+                    block.max_ts = block.max_ts.max(ts);
+                }
+                if !index.sources.contains(&line.src) {
+                    index.sources.insert(line.src.clone());
+                }
+                if !index.pids.contains(&line.pid) {
+                    index.pids.insert(line.pid.clone());
+                }
+            }
+            index.records += lines.len();
+            index.blocks.push(block);
+        }
+        index
+    }
+
+    // the byte ranges of the blocks which might contain the records in the time range, adjacent ones merged
+    fn ranges(&self, from: i64, to: i64) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.max_ts < from || block.min_ts > to {
+                continue;
+            }
+            let end = self.blocks.get(i + 1).map_or(self.size, |next| next.offset);
+            match ranges.last_mut() {
+                Some(last) if last.1 == block.offset => last.1 = end,
+                _ => ranges.push((block.offset, end)),
+            }
+        }
+        ranges
+    }
+}
+
+// the offsets of the records at regular intervals with their timestamps, and the sources and PIDs per log file,
+// so that the queries could seek to the records directly instead of parsing the whole folder
+// Example:
+//   file	1.uihlog	22677	12638187200555641636	106	28800	200
+//   sources	Recon	ScanSrv	UI
+//   pids	1234	5678
+//   block	106	1641013262865	1641013265000
+pub struct LogIndex {
+    files: BTreeMap<String, FileIndex>,     // file name => index
+}
+
+impl LogIndex {
+    pub fn new() -> Self {
+        LogIndex {
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(folder: &Path) -> Self {
+        match fs::read_to_string(folder.join(INDEX_FILE)) {
+            Ok(content) => Self::parse(&content).unwrap_or_else(|| {
+                eprintln!("invalid index file, build it again");
+                Self::new()
+            }),
+            Err(_) => Self::new(),
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut index = Self::new();
+        let mut current: Option<FileIndex> = None;
+        let mut current_name = String::new();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[0] {
+                "file" if fields.len() == 7 => {
+                    if let Some(file) = current.take() {
+                        index.files.insert(current_name, file);
+                    }
+                    current_name = fields[1].to_string();
+                    current = Some(FileIndex {
+                        size: fields[2].parse().ok()?,
+                        hash: fields[3].parse().ok()?,
+                        header_length: fields[4].parse().ok()?,
+                        tz: FixedOffset::east_opt(fields[5].parse().ok()?)?,
+                        records: fields[6].parse().ok()?,
+                        sources: BTreeSet::new(),
+                        pids: BTreeSet::new(),
+                        blocks: Vec::new(),
+                    });
+                },
+                "sources" => current.as_mut()?.sources.extend(fields[1..].iter().map(|src| src.to_string())),
+                "pids" => current.as_mut()?.pids.extend(fields[1..].iter().map(|pid| pid.to_string())),
+                "block" if fields.len() == 4 => current.as_mut()?.blocks.push(Block {
+                    offset: fields[1].parse().ok()?,
+                    min_ts: fields[2].parse().ok()?,
+                    max_ts: fields[3].parse().ok()?,
+                }),
+                _ => return None,
+            }
+        }
+        if let Some(file) = current {
+            index.files.insert(current_name, file);
+        }
+        Some(index)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (name, file) in &self.files {
+            text.push_str(&format!("file\t{}\t{}\t{}\t{}\t{}\t{}\n", name, file.size, file.hash, file.header_length, file.tz.local_minus_utc(), file.records));
+            for (key, values) in [("sources", &file.sources), ("pids", &file.pids)] {
+                text.push_str(key);
+                for value in values {
+                    text.push('\t');
+                    text.push_str(value);
+                }
+                text.push('\n');
+            }
+            for block in &file.blocks {
+                text.push_str(&format!("block\t{}\t{}\t{}\n", block.offset, block.min_ts, block.max_ts));
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        fs::write(folder.join(INDEX_FILE), self.to_text())
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    fn files(folder: &Path) -> Vec<PathBuf> {
+        let mut file_list = SortedFileList::new(folder);
+        let mut files = Vec::with_capacity(file_list.count());
+        while let Some(file) = file_list.next() {
+            files.push(file);
+        }
+        files
+    }
+
+    // index the new and the changed log files, and forget the removed ones, return whether anything changed
+    pub fn update(&mut self, folder: &Path) -> io::Result<bool> {
+        let files = Self::files(folder);
+        let names: BTreeSet<String> = files.iter().map(|file| Self::file_name(file)).collect();
+        let count = self.files.len();
+        self.files.retain(|name, _| names.contains(name));
+        let mut changed = self.files.len() != count;
+
+        for path in files {
+            let name = Self::file_name(&path);
+            let (size, prefix) = identify(&path)?;
+            if self.files.get(&name).is_some_and(|file| file.size == size && file.hash == hash_prefix(&prefix)) {
+                continue;
+            }
+            eprintln!("indexing {:?}", name);
+            self.files.insert(name, FileIndex::build(&read_bytes(&path)?));
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    // hand over the records matching the filter in file order, only the blocks in the time range are parsed
+    pub fn query<F>(&self, folder: &Path, filter: &LogFilter, mut handle: F) -> io::Result<()>
+    where F: FnMut(Vec<LogLine>) -> io::Result<()>,
+    {
+        for path in Self::files(folder) {
+            let file = match self.files.get(&Self::file_name(&path)) {
+                Some(file) => file,
+                None => continue,
+            };
+            if !file.sources.iter().any(|src| filter.accept_source(src)) {
+                continue;
+            }
+
+            let (from, to) = filter.time_range(&file.tz);
+            let ranges = file.ranges(from, to);
+            if ranges.is_empty() {
+                continue;
+            }
+            let mut parser = LogParser::new();
+            parser.parse_appended(&read_range(&path, 0, file.header_length)?, true);
+            for (start, end) in ranges {
+                let (lines, _) = parser.parse_appended(&read_range(&path, start, end - start)?, true);
+                handle(lines.into_iter().filter(|line| filter.accept(line)).collect())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (name, file) in &self.files {
+            let min_ts = file.blocks.iter().map(|block| block.min_ts).min().unwrap_or(i64::MAX);
+            let max_ts = file.blocks.iter().map(|block| block.max_ts).max().unwrap_or(i64::MIN);
+            let range = match min_ts <= max_ts {
+                true => format!("{} - {}", format_timestamp(min_ts, &file.tz), format_timestamp(max_ts, &file.tz)),
+                false => "INVALID_TS".to_string(),
+            };
+            writeln!(w, "{}: {} records in {} blocks, {}", name, file.records, file.blocks.len(), range)?;
+            writeln!(w, "    sources: {}", file.sources.iter().cloned().collect::<Vec<String>>().join(", "))?;
+            writeln!(w, "    pids: {}", file.pids.iter().cloned().collect::<Vec<String>>().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn index_folder(folder: &Path) -> io::Result<()> {
+    let mut index = LogIndex::load(folder);
+    if index.update(folder)? {
+        index.save(folder)?;
+    }
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    index.report(&mut w)
+}
+
+pub fn query_folder(folder: &Path, filter: &LogFilter, color: ColorMode) -> io::Result<()> {
+    let mut index = LogIndex::load(folder);
+    if index.update(folder)? {
+        index.save(folder)?;
+    }
+
+    let stdout = io::stdout();
+    let mut output = ConsoleOutput::new(stdout.lock(), color.use_color());
+    index.query(folder, filter, |lines| {
+        for line in lines {
+            output.send(&line)?;
+        }
+        Ok(())
+    })?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts: i64, src: &str) -> String {
+        let fields = ["LOG_DEV_INFO", &ts.to_string(), &format!("{}(1:2)", src), "file.cpp", "1", "FOO", "0X2001", "DESC", &ts.to_string()];
+        fields.join("\x02") + "\x01\n"
+    }
+
+    fn log_file(count: usize) -> String {
+        let mut data = String::from("timezone: (UTC+08:00)\x03\n");
+        for i in 0..count {
+            data.push_str(&record(1641013262000 + i as i64 * 1000, if i % 2 == 0 { "SRC1" } else { "SRC2" }));
+        }
+        data
+    }
+
+    #[test]
+    fn it_works() {
+        let data = log_file(BLOCK_RECORDS * 2 + 10);
+        let file = FileIndex::build(data.as_bytes());
+        assert!(file.records == BLOCK_RECORDS * 2 + 10);
+        assert!(file.blocks.len() == 3);
+        assert!(file.header_length == 23 && file.blocks[0].offset == 23);
+        assert!(file.tz == FixedOffset::east_opt(8 * 3600).unwrap());
+        assert!(file.sources.len() == 2 && file.pids.len() == 1);
+        assert!(file.blocks[1].min_ts == 1641013262000 + BLOCK_RECORDS as i64 * 1000);
+
+        // only the second block, then the second and the third ones merged
+        let ranges = file.ranges(file.blocks[1].min_ts + 1, file.blocks[1].min_ts + 2);
+        assert!(ranges == vec![(file.blocks[1].offset, file.blocks[2].offset)]);
+        let ranges = file.ranges(file.blocks[1].min_ts, i64::MAX);
+        assert!(ranges == vec![(file.blocks[1].offset, data.len() as u64)]);
+        assert!(file.ranges(0, 1).is_empty());
+
+        // the records of the block could be parsed alone with the header
+        let (start, end) = (file.blocks[2].offset as usize, data.len());
+        let mut parser = LogParser::new();
+        parser.parse_appended(&data.as_bytes()[..file.header_length as usize], true);
+        let (lines, _) = parser.parse_appended(&data.as_bytes()[start..end], true);
+        assert!(lines.len() == 10);
+    }
+
+    #[test]
+    fn persistence() {
+        let mut index = LogIndex::new();
+        index.files.insert("1.uihlog".into(), FileIndex::build(log_file(10).as_bytes()));
+
+        let text = index.to_text();
+        let loaded = LogIndex::parse(&text).unwrap();
+        assert!(loaded.to_text() == text);
+        assert!(text.contains("file\t1.uihlog\t"));
+        assert!(text.contains("sources\tSRC1\tSRC2\n"));
+
+        assert!(LogIndex::parse("sources\tSRC1\n").is_none());
+        assert!(LogIndex::parse("file\t1.uihlog\n").is_none());
+    }
+
+    #[test]
+    fn replaced_file() {
+        let folder = std::env::temp_dir().join(format!("uihlog_index_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("1.uihlog"), log_file(10)).unwrap();
+        let mut index = LogIndex::new();
+        assert!(index.update(&folder).unwrap());
+        assert!(!index.update(&folder).unwrap());
+
+        // replaced by another file of the same size
+        fs::write(folder.join("1.uihlog"), log_file(10).replace("SRC1", "SRC3")).unwrap();
+        let changed = index.update(&folder).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(changed);
+        assert!(index.files["1.uihlog"].sources.contains("SRC3"));
+    }
+
+    #[test]
+    fn compressed_file() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let folder = std::env::temp_dir().join(format!("uihlog_index_gz_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(log_file(BLOCK_RECORDS + 10).as_bytes()).unwrap();
+        fs::write(folder.join("1.uihlog.gz"), gz.finish().unwrap()).unwrap();
+
+        let mut index = LogIndex::new();
+        let changed = index.update(&folder);
+        let mut filter = LogFilter::new();
+        filter.add_source("SRC2");
+        let mut lines = Vec::new();
+        let queried = index.query(&folder, &filter, |mut parsed| {
+            lines.append(&mut parsed);
+            Ok(())
+        });
+        let unchanged = index.update(&folder);
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(changed.unwrap() && !unchanged.unwrap());
+        assert!(index.files["1.uihlog"].records == BLOCK_RECORDS + 10);
+        queried.unwrap();
+        assert!(lines.len() == (BLOCK_RECORDS + 10) / 2);
+    }
+}
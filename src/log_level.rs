@@ -0,0 +1,139 @@
+use std::fmt;
+
+// Example: LOG_DEV_WARNING => category DEV, severity WARNING
+const LEVEL_PREFIX: &str = "LOG_";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogCategory {
+    Dev,
+    Svc,
+    Trace,
+    Unknown,
+}
+
+impl LogCategory {
+    pub fn parse(buf: &str) -> LogCategory {
+        match buf.to_ascii_uppercase().as_str() {
+            "DEV" => LogCategory::Dev,
+            "SVC" => LogCategory::Svc,
+            "TRACE" => LogCategory::Trace,
+            _ => LogCategory::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogCategory::Dev => "DEV",
+            LogCategory::Svc => "SVC",
+            LogCategory::Trace => "TRACE",
+            LogCategory::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+// ordered from the least to the most severe, so that it could be used for minimum level filtering
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogSeverity {
+    Unknown,
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl LogSeverity {
+    pub fn parse(buf: &str) -> LogSeverity {
+        match buf.to_ascii_uppercase().as_str() {
+            "DEBUG" => LogSeverity::Debug,
+            "INFO" => LogSeverity::Info,
+            "WARN" | "WARNING" => LogSeverity::Warning,
+            "ERR" | "ERROR" => LogSeverity::Error,
+            "FATAL" => LogSeverity::Fatal,
+            _ => LogSeverity::Unknown,
+        }
+    }
+
+    // short names used in the parsed output, e.g. DEV_WARN
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogSeverity::Unknown => "UNKNOWN",
+            LogSeverity::Debug => "DEBUG",
+            LogSeverity::Info => "INFO",
+            LogSeverity::Warning => "WARN",
+            LogSeverity::Error => "ERROR",
+            LogSeverity::Fatal => "FATAL",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LogLevel {
+    pub category: LogCategory,
+    pub severity: LogSeverity,
+}
+
+impl LogLevel {
+    pub fn parse(buf: &str) -> LogLevel {
+        let buf = buf.trim();
+        let buf = buf.strip_prefix(LEVEL_PREFIX).unwrap_or(buf);
+        match buf.rfind('_') {
+            Some(idx) => LogLevel {
+                category: LogCategory::parse(&buf[..idx]),
+                severity: LogSeverity::parse(&buf[idx + 1..]),
+            },
+            None => LogLevel {
+                category: LogCategory::Unknown,
+                severity: LogSeverity::Unknown,
+            },
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.category != LogCategory::Unknown && self.severity != LogSeverity::Unknown
+    }
+}
+
+impl fmt::Display for LogLevel {
+    // padded to the width of the longest DEV/SVC level so that the parsed output keeps aligned
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_valid() {
+            let level = format!("{}_{}", self.category.as_str(), self.severity.as_str());
+            write!(f, "{:<9}", level)
+        } else {
+            write!(f, "UNKNOWN_LEVEL")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_parsing() {
+        assert_eq!(LogLevel::parse("LOG_DEV_WARNING"), LogLevel { category: LogCategory::Dev, severity: LogSeverity::Warning });
+        assert_eq!(LogLevel::parse("LOG_SVC_INFO"), LogLevel { category: LogCategory::Svc, severity: LogSeverity::Info });
+        assert_eq!(LogLevel::parse("LOG_TRACE_ERROR"), LogLevel { category: LogCategory::Trace, severity: LogSeverity::Error });
+        assert_eq!(LogLevel::parse("LOG_SVC_XFILE").severity, LogSeverity::Unknown);
+        assert!(!LogLevel::parse("LOG_SVC").is_valid());
+        assert!(!LogLevel::parse("").is_valid());
+    }
+
+    #[test]
+    fn level_formatting() {
+        assert_eq!(LogLevel::parse("LOG_DEV_WARNING").to_string(), "DEV_WARN ");
+        assert_eq!(LogLevel::parse("LOG_SVC_INFO").to_string(), "SVC_INFO ");
+        assert_eq!(LogLevel::parse("LOG_SVC_ERROR").to_string(), "SVC_ERROR");
+        assert_eq!(LogLevel::parse("LOG_TRACE_INFO").to_string(), "TRACE_INFO");
+        assert_eq!(LogLevel::parse("LOG_SVC_XFILE").to_string(), "UNKNOWN_LEVEL");
+        assert_eq!(LogLevel::parse("LOG_SVC").to_string(), "UNKNOWN_LEVEL");
+    }
+
+    #[test]
+    fn severity_ordering() {
+        assert!(LogSeverity::Info < LogSeverity::Warning);
+        assert!(LogSeverity::Warning < LogSeverity::Error);
+        assert!(LogSeverity::Unknown < LogSeverity::Debug);
+    }
+}
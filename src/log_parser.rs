@@ -1,22 +1,27 @@
 extern crate chrono;
 use chrono::prelude::*;
 
-use std::sync::mpsc;
+use crate::log_level::LogLevel;
+
+use std::io;
+use std::io::Read;
+use std::ops::Range;
 
 const HOUR: i32 = 3600;         // hour in seconds
 const MINUTE: i32 = 60;         // minute in seconds
 
 const MAX_LOGLINE_LENGTH: usize = 1024;                   // in bytes, for any line in the original .uihlog file
 const TYPICAL_LOGLINE_COUNT: usize = 100_000;
+const STREAM_CHUNK_SIZE: usize = 1024 * 1024;             // in bytes, read from a stream at a time
 
-const HEADER_END: &str  = "\x03\x0a";       // ETX (End of Text) + LF (\n)
+pub const HEADER_END: &str  = "\x03\x0a";   // ETX (End of Text) + LF (\n)
 const HEADER_END_OFFSET: usize = 2;
-const LOGGING_END: &str = "\x01\x0a";       // SOH (Start of Heading) + LF (\n)
+pub const LOGGING_END: &str = "\x01\x0a";   // SOH (Start of Heading) + LF (\n)
 const LOGGING_END2: &str = "\x01\x0aLOG";   // SOH (Start of Heading) + LF (\n) + "LOG"
 const LOGGING_END_OFFSET: usize = 2;
-const FIELD_DELIM: char = '\x02';           // STX (Start of Text)
+pub const FIELD_DELIM: char = '\x02';       // STX (Start of Text)
 
-enum LogField
+pub enum LogField
 {
     Level = 0,
     LocalTS,
@@ -30,20 +35,136 @@ enum LogField
     FieldCount,
 }
 
-impl Into<usize> for LogField {
-    fn into(self) -> usize {
-        self as usize
+impl From<LogField> for usize {
+    fn from(field: LogField) -> usize {
+        field as usize
     }
 }
 
 pub struct LogLine {
+    pub level: LogLevel,
+    pub local_ts: Option<i64>,      // in milliseconds since the Unix epoch
+    pub server_ts: Option<i64>,     // in milliseconds since the Unix epoch
+    pub tz: FixedOffset,            // timezone of the original log file
     pub src: String,
     pub pid: String,
-    pub content: Box<String>,
+    pub uid: String,
+    pub desc_range: Range<usize>,   // the description in the content, not copied for the lines only being split
+    pub content: String,
+}
+
+impl LogLine {
+    // the server-side timestamp is preferred since it's the first one in the parsed output
+    pub fn timestamp(&self) -> Option<i64> {
+        self.server_ts.or(self.local_ts)
+    }
+
+    pub fn desc(&self) -> &str {
+        &self.content[self.desc_range.clone()]
+    }
+}
+
+// a line to build in the tests of the other modules
+// Example: LogLine::test("DESC").with_src("Recon").with_ts(1641013262865)
+#[cfg(test)]
+impl LogLine {
+    pub fn test(desc: &str) -> LogLine {
+        LogLine {
+            level: LogLevel::parse("LOG_DEV_INFO"),
+            local_ts: None,
+            server_ts: None,
+            tz: FixedOffset::east_opt(0).unwrap(),
+            src: "SRC".into(),
+            pid: "1".into(),
+            uid: "0X2001".into(),
+            desc_range: 0..desc.len(),
+            content: format!("{}\n", desc),
+        }
+    }
+
+    pub fn with_level(mut self, level: &str) -> Self {
+        self.level = LogLevel::parse(level);
+        self
+    }
+
+    // both the local and the server timestamps
+    pub fn with_ts(mut self, ts: i64) -> Self {
+        self.local_ts = Some(ts);
+        self.server_ts = Some(ts);
+        self
+    }
+
+    pub fn with_local_ts(mut self, ts: i64) -> Self {
+        self.local_ts = Some(ts);
+        self
+    }
+
+    pub fn with_server_ts(mut self, ts: i64) -> Self {
+        self.server_ts = Some(ts);
+        self
+    }
+
+    pub fn with_tz(mut self, tz: FixedOffset) -> Self {
+        self.tz = tz;
+        self
+    }
+
+    pub fn with_src(mut self, src: &str) -> Self {
+        self.src = src.into();
+        self
+    }
+
+    pub fn with_pid(mut self, pid: &str) -> Self {
+        self.pid = pid.into();
+        self
+    }
+
+    pub fn with_uid(mut self, uid: &str) -> Self {
+        self.uid = uid.into();
+        self
+    }
+
+    // the content around the description, which is "{desc}\n" by default
+    pub fn with_content(mut self, head: &str, tail: &str) -> Self {
+        let desc = self.desc().to_string();
+        self.desc_range = head.len()..head.len() + desc.len();
+        self.content = format!("{}{}{}", head, desc, tail);
+        self
+    }
+}
+
+// replace the characters which are not allowed in file names
+pub fn to_file_stem(name: &str) -> String {
+    if name.contains(['/', '?', '\\', '*', ':']) {
+        // suppose it's a rare case, no further optimization
+        name.replace(['/', '?', '\\', '*', ':'], "_")
+    } else {
+        name.into()
+    }
+}
+
+// Example: 1641013262865 => 220101 13:01:02.865 (in UTC+08:00)
+pub fn format_timestamp(ts: i64, tz: &FixedOffset) -> String {
+    match Utc.timestamp_opt(ts.div_euclid(1000), 0).single() {
+        Some(utc) => format!("{}.{:03}", utc.with_timezone(tz).format("%y%m%d %H:%M:%S"), ts.rem_euclid(1000)),
+        None => "INVALID_TS".into(),
+    }
+}
+
+fn find_bytes(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+// in the same format as the parsed lines, e.g. "220101 13:01:02" or "220101 13:01:02.865"
+pub fn parse_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%y%m%d %H:%M:%S%.3f")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%y%m%d %H:%M:%S"))
+        .ok()
 }
 
 pub struct LogParser {
     tz: FixedOffset,
+    header_parsed: bool,
 
     // cache the last parsed timestamp for performance improvement
     last_timestamp_string: String,
@@ -53,58 +174,140 @@ pub struct LogParser {
 impl LogParser {
     pub fn new() -> LogParser {
         LogParser {
-            tz: Local.timestamp(0, 0).offset().fix(),
+            tz: Local.timestamp_opt(0, 0).unwrap().offset().fix(),
+            header_parsed: false,
             last_timestamp_string: String::new(),
             last_parsed_timestamp: String::new(),
         }
     }
 
-    pub fn parse_async(&mut self, content: String, sender: mpsc::Sender<Vec<LogLine>>) {
-        let lines = self.parse_buffer(&content);
-        if let Err(e) = sender.send(lines) {
-            println!("failed to send parsed result! {}", e.to_string());
+    pub fn parse_sync(&mut self, content: String) -> Vec<LogLine> {
+        self.parse_buffer(&content)
+    }
+
+    // the length of the header including its terminator, None if the header is incomplete
+    pub fn header_length(data: &[u8]) -> Option<usize> {
+        find_bytes(data, HEADER_END.as_bytes()).map(|idx| idx + HEADER_END_OFFSET)
+    }
+
+    // the offsets of all the records after the header, to seek into the file later
+    pub fn record_offsets(data: &[u8]) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        if let Some(mut start) = Self::header_length(data) {
+            while start < data.len() {
+                offsets.push(start);
+                match find_bytes(&data[start..], LOGGING_END2.as_bytes()) {
+                    Some(to) => start = start + to + LOGGING_END_OFFSET,
+                    None => break,
+                }
+            }
         }
+        offsets
     }
 
-    pub fn parse_sync(&mut self, content: String) -> Vec<LogLine> {
-        self.parse_buffer(&content)
+    // for a file still being written: parse the complete records at the beginning of the data,
+    // and return them with the length of the consumed data, the rest is expected to be completed later
+    // the last record is taken as complete only at the end of the input (e.g. the end of a stream or of a complete file),
+    // since a read could end right after a "\x01\n" in the description of a record
+    pub fn parse_appended(&mut self, data: &[u8], at_end: bool) -> (Vec<LogLine>, usize) {
+        let mut lines = Vec::<LogLine>::new();
+        let mut start = 0;
+        if !self.header_parsed {
+            match find_bytes(data, HEADER_END.as_bytes()) {
+                Some(idx) => {
+                    self.parse_header(&String::from_utf8_lossy(&data[..idx]));
+                    start = idx + HEADER_END_OFFSET;
+                },
+                None => return (lines, 0),
+            }
+        }
+
+        // a record is known to be complete only if the next one has started, or it's the last one of the input
+        let mut end = start;
+        let mut from = start;
+        while let Some(idx) = find_bytes(&data[from..], LOGGING_END.as_bytes()) {
+            let next = from + idx + LOGGING_END_OFFSET;
+            if (at_end && next == data.len()) || data[next..].starts_with(b"LOG") {
+                end = next;
+            }
+            from = next;
+        }
+
+        if end > start {
+            let records = String::from_utf8_lossy(&data[start..end]);  // the delimiters are ASCII, no character is split
+            self.parse_records(&records, &mut lines);
+        }
+        (lines, end)
+    }
+
+    // parse a stream (e.g. stdin) chunk by chunk without holding the whole data, and hand over the lines of each chunk
+    pub fn parse_stream<R: Read, F>(&mut self, mut r: R, mut handle: F) -> io::Result<()>
+    where F: FnMut(Vec<LogLine>) -> io::Result<()>,
+    {
+        let mut pending: Vec<u8> = Vec::new();
+        let mut chunk = vec![0u8; STREAM_CHUNK_SIZE];
+        loop {
+            let length = match r.read(&mut chunk) {
+                Ok(length) => length,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if length == 0 {
+                // the last record is complete now, an incomplete one at the end is dropped, as parse_buffer does
+                let (lines, _) = self.parse_appended(&pending, true);
+                return match lines.is_empty() {
+                    true => Ok(()),
+                    false => handle(lines),
+                };
+            }
+            pending.extend_from_slice(&chunk[..length]);
+            let (lines, consumed) = self.parse_appended(&pending, false);
+            pending.drain(..consumed);
+            if !lines.is_empty() {
+                handle(lines)?;
+            }
+        }
     }
 
     fn parse_buffer(&mut self, data: &str) -> Vec<LogLine> {
         let mut lines = Vec::<LogLine>::with_capacity(TYPICAL_LOGLINE_COUNT);
         if let Some(idx) = data.find(HEADER_END) {
             self.parse_header(&data[0..idx]);
+            self.parse_records(&data[idx + HEADER_END_OFFSET..], &mut lines);
+        }
+        lines
+    }
 
-            let mut start = idx + HEADER_END_OFFSET;
-            loop {
-                match data[start..].find(LOGGING_END2) {   // in case there is invalid content in the log description
-                    Some(to) => {
+    fn parse_records(&mut self, data: &str, lines: &mut Vec<LogLine>) {
+        let mut start = 0;
+        loop {
+            match data[start..].find(LOGGING_END2) {   // in case there is invalid content in the log description
+                Some(to) => {
+                    if let Some(line) = self.parse_line(&data[start .. start + to]) {
+                        lines.push(line);
+                    }
+                    start = start + to + LOGGING_END_OFFSET;
+                }
+                None => {
+                    if let Some(to) = data[start..].rfind(LOGGING_END) {  // for the last line, which may contain LOGGING_END as well
                         if let Some(line) = self.parse_line(&data[start .. start + to]) {
                             lines.push(line);
                         }
-                        start = start + to + LOGGING_END_OFFSET;
-                    }
-                    None => {
-                        if let Some(to) = data[start..].find(LOGGING_END) {  // for the last line
-                            if let Some(line) = self.parse_line(&data[start .. start + to]) {
-                                lines.push(line);
-                            }
-                        }
-                        break
                     }
+                    break
                 }
             }
         }
-        lines
     }
 
     fn parse_header(&mut self, header: &str) {
+        self.header_parsed = true;
         if let Some(start) = header.find("(UTC") {
             if let Some(end) = header[start..].find(")") {
                 self.tz = Self::parse_timezone(&header[start + 4 .. start + end]);
             }
         } else {
-            println!("failed to locate timezone info! will use the local timezone instead.")
+            eprintln!("failed to locate timezone info! will use the local timezone instead.")
         }
     }
 
@@ -112,27 +315,28 @@ impl LogParser {
         if let Ok(hh) = &tz[1..3].parse::<i32>() {
             if let Ok(mm) = &tz[4..6].parse::<i32>() {
                 if &tz[0..1] == "+" || (*hh == 0 && *mm == 0) {
-                    return FixedOffset::east(hh * HOUR + mm * MINUTE);
+                    return FixedOffset::east_opt(hh * HOUR + mm * MINUTE).unwrap();   // safe since the offset is within a day
                 } else {
-                    return FixedOffset::west(hh * HOUR + mm * MINUTE);
+                    return FixedOffset::west_opt(hh * HOUR + mm * MINUTE).unwrap();
                 }
             }
         }
-        println!("failed to parse timezone info! will use the local timezone instead.");
-        Local.timestamp(0, 0).offset().fix()
+        eprintln!("failed to parse timezone info! will use the local timezone instead.");
+        Local.timestamp_opt(0, 0).unwrap().offset().fix()
     }
 
     fn parse_line(&mut self, line: &str) -> Option<LogLine> {
         let fields: Vec<&str> = line.split(FIELD_DELIM).collect();
         if fields.len() < LogField::FieldCount.into() {
-            println!("invalid log line!");
+            eprintln!("invalid log line!");
             return None;
         }
 
         // much faster than using '+' to contatenate strings (about one order of magnitude difference)
+        let level = LogLevel::parse(fields[LogField::Level as usize]);
         let mut line = String::with_capacity(MAX_LOGLINE_LENGTH);
-        line.push_str(&Self::parse_level(fields[LogField::Level as usize]));
-        line.push_str(" ");
+        line.push_str(&level.to_string());
+        line.push(' ');
 
         // in case there are unexpected field delimiters ('\x02') in the log description
         let server_ts = if fields.len() > LogField::FieldCount as usize {
@@ -147,66 +351,48 @@ impl LogParser {
         line.push_str("] ");
 
         line.push_str(fields[LogField::SrcPidTid as usize]);
-        line.push_str(" ");
+        line.push(' ');
 
         let desc = fields[LogField::Description as usize];
-        if let Some(_) = desc.rfind(|c| c == '\n' || c == '\r') {
-            let desc = desc.replace('\n', " ").replace('\r', " ");
-            line.push_str(&desc);
+        let desc_start = line.len();
+        if desc.contains(['\n', '\r']) {
+            line.push_str(&desc.replace(['\n', '\r'], " "));
         } else {
-            line.push_str(&desc);
+            line.push_str(desc);
         }
+        let desc_range = desc_start..line.len();
 
         line.push_str(" [");
         line.push_str(fields[LogField::Function as usize]);
-        line.push_str(" ");
+        line.push(' ');
         line.push_str(fields[LogField::FileName as usize]);
-        line.push_str(" ");
+        line.push(' ');
         line.push_str(fields[LogField::LineNo as usize]);
         line.push_str("] [");
         line.push_str(fields[LogField::Uid as usize]);
         line.push_str("]\n");
 
         Some(LogLine {
+            level,
+            local_ts: fields[LogField::LocalTS as usize].parse::<i64>().ok(),
+            server_ts: fields[server_ts].parse::<i64>().ok(),
+            tz: self.tz,
             src: Self::parse_src(fields[LogField::SrcPidTid as usize]),
             pid: Self::parse_pid(fields[LogField::SrcPidTid as usize]).into(),
-            content: Box::new(line),
+            uid: fields[LogField::Uid as usize].to_string(),
+            desc_range,
+            content: line,
         })
     }
 
-    // Example: LOG_DEV_WARNING => DEV_WARN, etc.
-    fn parse_level(buf: &str) -> String {
-        if let Some(ch) = buf.chars().nth(8) {
-            return match ch {
-                // INFO or WARNING
-                'I' | 'W' => {
-                    let mut level: String = buf[4..12].into();
-                    level.push_str(" ");
-                    level
-                },
-                // ERROR (LOG_TRACE_*** would also fall into this category, let it be...)
-                'E' => buf[4..13].into(),
-                _ => "UNKNOWN_LEVEL".into(),
-            }
-        }
-        "INVALID_LEVEL".into()
-    }
-
     fn parse_src(buf: &str) -> String {
         if let Some(src_end) = buf.find('(') {
-            let src = &buf[..src_end];
-
-            if let Some(_) = src.find(|c| c == '/' || c == '?' || c == '\\' || c == '*' || c == ':') {
-                // suppose it's a rare case, no further optimization
-                return src.replace('/', "_").replace('?', "_").replace('\\', "_").replace('*', "_").replace(':', "_");
-            } else {
-                return src.into();
-            }
+            return to_file_stem(&buf[..src_end]);
         }
         "INVALID_SRC".into()
     }
 
-    fn parse_pid<'a>(buf: &'a str) -> &'a str {
+    fn parse_pid(buf: &str) -> &str {
         if let Some(src_end) = buf.find('(') {
             if let Some(pid_end) = buf[src_end..].find(':') {
                 let pid = &buf[src_end + 1 .. src_end + pid_end];
@@ -224,9 +410,7 @@ impl LogParser {
             return self.last_parsed_timestamp.clone() + "." + msec;
         }
 
-        if let Ok(sec) = sec.parse::<i64>() {
-            let naive = NaiveDateTime::from_timestamp(sec, 0);
-            let utc: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+        if let Some((sec, utc)) = sec.parse::<i64>().ok().and_then(|sec| Utc.timestamp_opt(sec, 0).single().map(|utc| (sec, utc))) {
             self.last_timestamp_string = sec.to_string();
             self.last_parsed_timestamp = utc.with_timezone(&self.tz).format("%y%m%d %H:%M:%S").to_string();
             self.last_parsed_timestamp.clone() + "." + msec
@@ -239,25 +423,20 @@ impl LogParser {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::mpsc;
 
     #[test]
     fn timezone_parsing() {
-        assert!(LogParser::parse_timezone("+08:00") == FixedOffset::east(8 * HOUR));
-        assert!(LogParser::parse_timezone("+00:00") == FixedOffset::east(0 * HOUR));
-        assert!(LogParser::parse_timezone("-00:00") == FixedOffset::east(0 * HOUR));
-        assert!(LogParser::parse_timezone("-07:30") == FixedOffset::west(7 * HOUR + 30 * MINUTE));
-        assert!(LogParser::parse_timezone("BAD_TZ") == Local.timestamp(0, 0).offset().fix());
+        assert!(LogParser::parse_timezone("+08:00") == FixedOffset::east_opt(8 * HOUR).unwrap());
+        assert!(LogParser::parse_timezone("+00:00") == FixedOffset::east_opt(0).unwrap());
+        assert!(LogParser::parse_timezone("-00:00") == FixedOffset::east_opt(0).unwrap());
+        assert!(LogParser::parse_timezone("-07:30") == FixedOffset::west_opt(7 * HOUR + 30 * MINUTE).unwrap());
+        assert!(LogParser::parse_timezone("BAD_TZ") == Local.timestamp_opt(0, 0).unwrap().offset().fix());
     }
 
     #[test]
-    fn level_parsing() {
-        assert!(LogParser::parse_level("LOG_DEV_WARNING") == "DEV_WARN ");
-        assert!(LogParser::parse_level("LOG_SVC_INFO") == "SVC_INFO ");
-        assert!(LogParser::parse_level("LOG_SVC_ERROR") == "SVC_ERROR");
-        assert!(LogParser::parse_level("LOG_SVC_XFILE") == "UNKNOWN_LEVEL");
-        assert!(LogParser::parse_level("LOG_SVC") == "INVALID_LEVEL");
-        assert!(LogParser::parse_level("LOG_TRACE_INFO") == "TRACE_INF")
+    fn timestamp_formatting() {
+        assert!(format_timestamp(1641013262865, &FixedOffset::east_opt(8 * HOUR).unwrap()) == "220101 13:01:02.865");
+        assert!(format_timestamp(1641013262005, &FixedOffset::west_opt(HOUR).unwrap()) == "220101 04:01:02.005");
     }
 
     #[test]
@@ -290,17 +469,110 @@ mod tests {
         logfile.push_str(LOGGING_END);
 
         let mut parser = LogParser::new();
-        let (tx, rx) = mpsc::channel::<Vec<LogLine>>();
-        parser.parse_async(logfile, tx);
-        let lines = rx.recv().unwrap();
+        let lines = parser.parse_sync(logfile);
         assert!(lines.len() == 2);
 
         assert!(lines[0].src == "SRC1");
         assert!(lines[0].pid == "1");
         assert!(lines[0].content.as_str() == "DEV_INFO  220101 13:01:02.865 [120904 07:21:31.516] SRC1(1:2) DESC1 [FOO1 file1.cpp 128] [0X2001]\n");
 
+        assert!(lines[0].local_ts == Some(1346714491516));
+        assert!(lines[0].timestamp() == Some(1641013262865));
+        assert!(lines[0].desc() == "DESC1");
+        assert!(lines[0].uid == "0X2001");
+
         assert!(lines[1].src == "SRC2");
         assert!(lines[1].pid == "3");
+        assert!(lines[1].timestamp() == Some(1641013262865));
+        assert!(lines[1].desc() == "DESC2 MORE EVEN MORE");
         assert!(lines[1].content.as_str() == "DEV_INFO  INVALID_TS [220101 13:01:02.865] SRC2(3:4) DESC2 MORE EVEN MORE [FOO2 file2.cpp 256] [0X2002]\n");
     }
+
+    #[test]
+    fn appended_parsing() {
+        let fields1: Vec<&str> = vec!["LOG_DEV_INFO", "1641013262865", "SRC1(1:2)", "file1.cpp", "128", "FOO1", "0X2001", "DESC1", "1641013262865"];
+        let fields2: Vec<&str> = vec!["LOG_DEV_INFO", "1641013262866", "SRC2(3:4)", "file2.cpp", "256", "FOO2", "0X2002", "DESC2", "1641013262866"];
+        let record1 = fields1.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END;
+        let record2 = fields2.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END;
+
+        let mut parser = LogParser::new();
+        let (lines, consumed) = parser.parse_appended(b"timezone: (UTC+08:00)", true);
+        assert!(lines.is_empty() && consumed == 0);
+
+        // the first record is complete only when the second one has started
+        let mut data = String::from("timezone: (UTC+08:00)") + HEADER_END + &record1[..record1.len() - 1];
+        let (lines, consumed) = parser.parse_appended(data.as_bytes(), false);
+        assert!(lines.is_empty() && consumed == 21 + HEADER_END_OFFSET);
+        data.drain(..consumed);
+
+        data.push_str("\nLO");
+        let (lines, consumed) = parser.parse_appended(data.as_bytes(), false);
+        assert!(lines.is_empty() && consumed == 0);
+
+        // the second record may go on in the next read, until the end of the input
+        data.push_str(&record2[2..]);
+        let (lines, consumed) = parser.parse_appended(data.as_bytes(), false);
+        assert!(lines.len() == 1 && consumed == record1.len());
+        assert!(lines[0].content.as_str() == "DEV_INFO  220101 13:01:02.865 [220101 13:01:02.865] SRC1(1:2) DESC1 [FOO1 file1.cpp 128] [0X2001]\n");
+        data.drain(..consumed);
+
+        let (lines, consumed) = parser.parse_appended(data.as_bytes(), true);
+        assert!(lines.len() == 1 && consumed == data.len());
+        assert!(lines[0].src == "SRC2");
+
+        let data = String::from("timezone: (UTC+08:00)") + HEADER_END + &record1 + &record2;
+        let offsets = LogParser::record_offsets(data.as_bytes());
+        assert!(offsets == vec![21 + HEADER_END_OFFSET, 21 + HEADER_END_OFFSET + record1.len()]);
+    }
+
+    // a stream giving a few bytes at a time, like a slow pipe
+    struct SlowStream<'a> {
+        data: &'a [u8],
+    }
+
+    impl<'a> Read for SlowStream<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let length = std::cmp::min(std::cmp::min(buf.len(), 5), self.data.len());
+            buf[..length].copy_from_slice(&self.data[..length]);
+            self.data = &self.data[length..];
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn stream_parsing() {
+        let fields1: Vec<&str> = vec!["LOG_DEV_INFO", "1641013262865", "SRC1(1:2)", "file1.cpp", "128", "FOO1", "0X2001", "DESC1", "1641013262865"];
+        let fields2: Vec<&str> = vec!["LOG_DEV_INFO", "1641013262866", "SRC2(3:4)", "file2.cpp", "256", "FOO2", "0X2002", "DESC2", "1641013262866"];
+        let data = String::from("timezone: (UTC+08:00)") + HEADER_END
+            + &fields1.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END
+            + &fields2.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END
+            + "LOG_DEV_INFO\x02INCOMPLETE";
+
+        let mut streamed: Vec<LogLine> = Vec::new();
+        LogParser::new().parse_stream(SlowStream { data: data.as_bytes() }, |mut lines| {
+            streamed.append(&mut lines);
+            Ok(())
+        }).unwrap();
+        let parsed = LogParser::new().parse_sync(data);
+        assert!(streamed.len() == 2);
+        assert!(streamed.iter().map(|line| &line.content).eq(parsed.iter().map(|line| &line.content)));
+    }
+
+    #[test]
+    fn stream_split_in_desc() {
+        // the description contains the record terminator, and a read ends right after it
+        let fields: Vec<&str> = vec!["LOG_DEV_INFO", "1641013262865", "SRC1(1:2)", "file1.cpp", "128", "FOO1", "0X2001", "DE\x01\nSC1", "1641013262865"];
+        let header = String::from("timezone: (UTC+08:00)") + HEADER_END;
+        let record = fields.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END;
+        let split = header.len() + record.find("SC1").unwrap();
+
+        let mut parser = LogParser::new();
+        let mut streamed: Vec<LogLine> = Vec::new();
+        let data = header + &record;
+        parser.parse_stream(io::Cursor::new(&data.as_bytes()[..split]).chain(&data.as_bytes()[split..]), |mut lines| {
+            streamed.append(&mut lines);
+            Ok(())
+        }).unwrap();
+        assert!(streamed.len() == 1 && streamed[0].desc() == "DE\x01 SC1");
+    }
 }
@@ -0,0 +1,163 @@
+use crate::log_parser::LogLine;
+use crate::log_writer::LogRecord;
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// the sensitive values found in the logs, the value is the first group of the pattern if it has one
+// the DICOM UIDs go first, since the parts of them look like IP addresses
+pub const DETECTORS: [(&str, &str); 5] = [
+    ("UID", r"\b[12](?:\.(?:0|[1-9][0-9]*)){4,}\b"),
+    ("IP", r"\b(?:(?:25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9]?[0-9])\b"),
+    ("MAC", r"\b[0-9A-Fa-f]{2}(?:[:-][0-9A-Fa-f]{2}){5}\b"),
+    ("DOB", r"(?i)\b(?:patient\s*)?(?:dob|birth\s*date|date\s+of\s+birth)\s*[:=]?\s*([0-9]{4}[-/.]?[0-9]{2}[-/.]?[0-9]{2}|[0-9]{2}[-/.][0-9]{2}[-/.][0-9]{4})"),
+    ("USER", r#"(?i)\b[A-Z]:[\\/](?:Users|Documents and Settings)[\\/]([^\\/:*?"<>|\s]+)"#),
+];
+const USER_PATTERN: &str = "REDACTED";
+
+// the same value always maps to the same token, numbered per kind in the order of appearance
+// Example: "192.168.1.10" => "<IP_1>"
+struct Pseudonyms {
+    tokens: HashMap<String, String>,
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Pseudonyms {
+    fn token(&mut self, kind: &'static str, value: &str) -> String {
+        if let Some(token) = self.tokens.get(value) {
+            return token.clone();
+        }
+        let count = self.counts.entry(kind).or_insert(0);
+        *count += 1;
+        let token = format!("<{}_{}>", kind, count);
+        self.tokens.insert(value.to_string(), token.clone());
+        token
+    }
+
+    fn replace(&mut self, kind: &'static str, caps: &Captures) -> String {
+        let whole = caps.get(0).unwrap();
+        match caps.get(1) {
+            Some(value) => format!("{}{}{}",
+                &whole.as_str()[..value.start() - whole.start()],
+                self.token(kind, value.as_str()),
+                &whole.as_str()[value.end() - whole.start()..]),
+            None => self.token(kind, whole.as_str()),
+        }
+    }
+}
+
+// replace the sensitive values in the parsed lines by the pseudonyms, keep one for the whole folder
+pub struct LogRedactor {
+    detectors: Vec<(&'static str, Regex)>,
+    pseudonyms: Pseudonyms,
+}
+
+impl LogRedactor {
+    // the built-in detectors are enabled with or without the user patterns, nothing is redacted if not enabled
+    pub fn new(enabled: bool, patterns: &[Regex]) -> Self {
+        let mut detectors: Vec<(&'static str, Regex)> = Vec::new();
+        if enabled {
+            for (kind, pattern) in DETECTORS.iter() {
+                detectors.push((kind, Regex::new(pattern).unwrap()));   // safe since they are tested
+            }
+        }
+        for pattern in patterns {
+            detectors.push((USER_PATTERN, pattern.clone()));
+        }
+        LogRedactor {
+            detectors,
+            pseudonyms: Pseudonyms { tokens: HashMap::new(), counts: HashMap::new() },
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.detectors.is_empty()
+    }
+
+    pub fn redact_text(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        let pseudonyms = &mut self.pseudonyms;
+        for (kind, regex) in &self.detectors {
+            let redacted = match regex.replace_all(&text, |caps: &Captures| pseudonyms.replace(kind, caps)) {
+                Cow::Owned(redacted) => Some(redacted),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(redacted) = redacted {
+                text = redacted;
+            }
+        }
+        text
+    }
+
+    pub fn redact(&mut self, line: &mut LogLine) {
+        if self.is_enabled() {
+            // the parts around the description are redacted apart, so that it's still found in the content
+            let head = self.redact_text(&line.content[..line.desc_range.start]);
+            let desc = self.redact_text(line.desc());
+            let tail = self.redact_text(&line.content[line.desc_range.end..]);
+            line.desc_range = head.len()..head.len() + desc.len();
+            line.content = head + &desc + &tail;
+        }
+    }
+
+    // every text field of a record to write out again, the timestamps and the line number are kept
+    pub fn redact_record(&mut self, record: &mut LogRecord) {
+        if self.is_enabled() {
+            for field in [&mut record.level, &mut record.src, &mut record.pid, &mut record.tid,
+                &mut record.file, &mut record.function, &mut record.uid, &mut record.desc] {
+                *field = self.redact_text(field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detectors() {
+        let mut redactor = LogRedactor::new(true, &[]);
+        assert!(redactor.redact_text("connected to 192.168.1.10:104 from 10.0.0.1") == "connected to <IP_1>:104 from <IP_2>");
+        assert!(redactor.redact_text("study 1.2.840.113619.2.55.3 at 192.168.1.10") == "study <UID_1> at <IP_1>");
+        assert!(redactor.redact_text("nic 00:1A:2b:3C:4d:5E") == "nic <MAC_1>");
+        assert!(redactor.redact_text("PatientBirthDate=19800102, DOB: 02/01/1980") == "PatientBirthDate=<DOB_1>, DOB: <DOB_2>");
+        assert!(redactor.redact_text("saved to C:\\Users\\john.doe\\scan.dcm") == "saved to C:\\Users\\<USER_1>\\scan.dcm");
+
+        // the timestamps and versions of the parsed lines are kept
+        assert!(redactor.redact_text("220101 13:01:02.865 v1.2.3") == "220101 13:01:02.865 v1.2.3");
+    }
+
+    #[test]
+    fn pseudonyms() {
+        let name = Regex::new(r"patient ([A-Z][a-z]+)").unwrap();
+        let mut redactor = LogRedactor::new(true, &[name]);
+        assert!(redactor.redact_text("patient Smith from 10.0.0.1") == "patient <REDACTED_1> from <IP_1>");
+        assert!(redactor.redact_text("patient Jones from 10.0.0.2") == "patient <REDACTED_2> from <IP_2>");
+        assert!(redactor.redact_text("patient Smith from 10.0.0.1") == "patient <REDACTED_1> from <IP_1>");
+
+        let mut redactor = LogRedactor::new(false, &[]);
+        assert!(!redactor.is_enabled());
+        assert!(redactor.redact_text("10.0.0.1") == "10.0.0.1");
+    }
+
+    #[test]
+    fn line_redaction() {
+        let mut redactor = LogRedactor::new(true, &[]);
+        let mut line = LogLine::test("sent to 10.0.0.1").with_content("SRC(1:2) from 10.0.0.2 ", " [Send C:\\Users\\john\\send.cpp 1]\n");
+        redactor.redact(&mut line);
+        assert!(line.desc() == "sent to <IP_2>");
+        assert!(line.content == "SRC(1:2) from <IP_1> sent to <IP_2> [Send C:\\Users\\<USER_1>\\send.cpp 1]\n");
+    }
+
+    #[test]
+    fn record_redaction() {
+        let mut redactor = LogRedactor::new(true, &[]);
+        let mut record = LogRecord::parse("LOG_DEV_INFO\x021641013262865\x02SRC(10.0.0.1:2)\x02C:\\Users\\john\\send.cpp\x0242\x02Send\x021.2.840.10008.1\x02sent to 10.0.0.1\x021641013262865").unwrap();
+        redactor.redact_record(&mut record);
+        assert!(record.pid == "<IP_1>" && record.desc == "sent to <IP_1>");
+        assert!(record.file == "C:\\Users\\<USER_1>\\send.cpp" && record.uid == "<UID_1>");
+        assert!(record.local_ts == 1641013262865 && record.line == 42);
+    }
+}
@@ -0,0 +1,387 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_level::LogSeverity;
+use crate::log_parser::{format_timestamp, parse_time, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+const MAX_REQUEST_LENGTH: u64 = 16 * 1024;                  // in bytes, for the request line and the headers
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);   // so that an idle client doesn't hold its thread forever
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: String) -> Self {
+        Response { status, content_type, body }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", status.to_string())
+    }
+}
+
+// Example: "a%20b+c" => "a b c"
+fn decode_url(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    },
+                    None => decoded.push(b'%'),
+                }
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(idx) => (decode_url(&pair[..idx]), decode_url(&pair[idx + 1..])),
+            None => (decode_url(pair), String::new()),
+        })
+        .collect()
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// serve the merged timeline of a log folder, to the web page and the JSON queries
+pub struct LogServer {
+    lines: Vec<LogLine>,    // merged by timestamp
+}
+
+impl LogServer {
+    pub fn new(mut lines: Vec<LogLine>) -> Self {
+        lines.sort_by_key(|line| line.timestamp().unwrap_or(i64::MIN));    // stable, so the file order is kept for the same timestamp
+        LogServer { lines }
+    }
+
+    // Example: /api/records?src=UI,Recon&level=warning&from=220101%2013:00:00&to=&text=timeout&offset=0&limit=100
+    fn records(&self, params: &BTreeMap<String, String>) -> Result<String, String> {
+        let mut filter = LogFilter::new();
+        let param = |key: &str| params.get(key).map(|value| value.trim()).filter(|value| !value.is_empty());
+        if let Some(level) = param("level") {
+            match LogSeverity::parse(level) {
+                LogSeverity::Unknown => return Err(format!("invalid level {}", level)),
+                severity => filter.set_min_severity(severity),
+            }
+        }
+        if let Some(sources) = param("src") {
+            for src in sources.split(',') {
+                filter.add_source(src.trim());
+            }
+        }
+        if let Some(from) = param("from") {
+            filter.set_from(parse_time(from).ok_or(format!("invalid time {}", from))?);
+        }
+        if let Some(to) = param("to") {
+            filter.set_to(parse_time(to).ok_or(format!("invalid time {}", to))?);
+        }
+        let text = param("text").unwrap_or("").to_lowercase();
+        let offset = param("offset").map_or(Ok(0), |value| value.parse::<usize>()).map_err(|_| "invalid offset".to_string())?;
+        let limit = param("limit").map_or(Ok(DEFAULT_PAGE_SIZE), |value| value.parse::<usize>()).map_err(|_| "invalid limit".to_string())?;
+
+        let matched: Vec<&LogLine> = self.lines.iter()
+            .filter(|line| filter.accept(line))
+            .filter(|line| text.is_empty() || line.content.to_lowercase().contains(&text))
+            .collect();
+
+        let records: Vec<String> = matched.iter().skip(offset).take(limit.min(MAX_PAGE_SIZE)).map(|line| {
+            let ts = line.timestamp().map_or("null".to_string(), |ts| ts.to_string());
+            let time = line.timestamp().map_or(String::new(), |ts| format_timestamp(ts, &line.tz));
+            format!("{{\"ts\":{},\"time\":\"{}\",\"level\":\"{}\",\"severity\":\"{}\",\"src\":\"{}\",\"pid\":\"{}\",\"uid\":\"{}\",\"desc\":\"{}\",\"line\":\"{}\"}}",
+                ts, time, line.level.to_string().trim_end(), line.level.severity.as_str(), escape_json(&line.src), escape_json(&line.pid),
+                escape_json(&line.uid), escape_json(line.desc()), escape_json(line.content.trim_end()))
+        }).collect();
+        Ok(format!("{{\"total\":{},\"offset\":{},\"records\":[{}]}}", matched.len(), offset, records.join(",")))
+    }
+
+    fn sources(&self) -> String {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for line in &self.lines {
+            *counts.entry(&line.src).or_default() += 1;
+        }
+        let sources: Vec<String> = counts.iter().map(|(src, count)| format!("{{\"src\":\"{}\",\"count\":{}}}", escape_json(src), count)).collect();
+        format!("[{}]", sources.join(","))
+    }
+
+    fn respond(&self, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error("405 Method Not Allowed");
+        }
+        let (path, query) = match target.find('?') {
+            Some(idx) => (&target[..idx], &target[idx + 1..]),
+            None => (target, ""),
+        };
+        match path {
+            "/" => Response::new("200 OK", "text/html; charset=utf-8", INDEX_PAGE.to_string()),
+            "/api/sources" => Response::new("200 OK", "application/json", self.sources()),
+            "/api/records" => match self.records(&parse_query(query)) {
+                Ok(body) => Response::new("200 OK", "application/json", body),
+                Err(e) => Response::new("400 Bad Request", "application/json", format!("{{\"error\":\"{}\"}}", escape_json(&e))),
+            },
+            _ => Response::error("404 Not Found"),
+        }
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let request = read_request(BufReader::new(stream.try_clone()?))?;
+
+        // Example: "GET /api/records?src=UI HTTP/1.1"
+        let response = match request {
+            Some(request) => {
+                let fields: Vec<&str> = request.split_whitespace().collect();
+                match fields.len() {
+                    3 => self.respond(fields[0], fields[1]),
+                    _ => Response::error("400 Bad Request"),
+                }
+            },
+            None => Response::error("431 Request Header Fields Too Large"),
+        };
+
+        write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status, response.content_type, response.body.len())?;
+        stream.write_all(response.body.as_bytes())?;
+        stream.flush()
+    }
+}
+
+// the request line, with the headers skipped, or None if they are longer than MAX_REQUEST_LENGTH
+fn read_request<R: BufRead>(reader: R) -> io::Result<Option<String>> {
+    let mut reader = reader.take(MAX_REQUEST_LENGTH);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        if header.trim_end().is_empty() {
+            return Ok(Some(request));
+        }
+    }
+
+    // the end of the stream, or the limit reached in the middle of the headers
+    match reader.limit() {
+        0 => Ok(None),
+        _ => Ok(Some(request)),
+    }
+}
+
+pub fn serve_folder(folder: &Path, filter: &LogFilter, port: u16, pool: &WorkerPool) -> io::Result<()> {
+    let mut lines: Vec<LogLine> = Vec::new();
+    parse_folder_with(folder, pool, |parsed| {
+        lines.extend(parsed.into_iter().filter(|line| filter.accept(line)));
+        Ok(())
+    })?;
+    let server = Arc::new(LogServer::new(lines));
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("serving {:?} on http://127.0.0.1:{}/, press Ctrl+C to stop", folder, port);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || {
+                    if let Err(e) = server.handle(stream) {
+                        eprintln!("failed to handle the request: {}", e);
+                    }
+                });
+            },
+            Err(e) => eprintln!("failed to accept the connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+const INDEX_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>uihlog</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+form { margin-bottom: 1em; }
+form input, form select { margin-right: 0.5em; }
+table { border-collapse: collapse; font-family: monospace; font-size: 12px; width: 100%; }
+td { padding: 1px 6px; white-space: pre; vertical-align: top; }
+tr:nth-child(even) { background: #f4f4f4; }
+.WARN { color: #b07000; }
+.ERROR, .FATAL { color: #c00000; font-weight: bold; }
+</style>
+</head>
+<body>
+<form id="query">
+  from <input name="from" placeholder="220101 13:00:00" size="18">
+  to <input name="to" placeholder="220101 13:05:00" size="18">
+  source <select name="src" id="sources"><option value="">all</option></select>
+  level <select name="level">
+    <option value="">all</option><option>info</option><option>warning</option><option>error</option>
+  </select>
+  text <input name="text" size="24">
+  <button type="submit">query</button>
+  <button type="button" id="prev">&lt;</button>
+  <button type="button" id="next">&gt;</button>
+  <span id="status"></span>
+</form>
+<table><tbody id="records"></tbody></table>
+<script>
+const PAGE = 100;
+let offset = 0;
+const form = document.getElementById('query');
+
+function cell(row, text) {
+  const td = document.createElement('td');
+  td.textContent = text;
+  row.appendChild(td);
+}
+
+async function load() {
+  const params = new URLSearchParams(new FormData(form));
+  params.set('offset', offset);
+  params.set('limit', PAGE);
+  const response = await fetch('/api/records?' + params);
+  const result = await response.json();
+  const status = document.getElementById('status');
+  if (result.error) {
+    status.textContent = result.error;
+    return;
+  }
+  const body = document.getElementById('records');
+  body.innerHTML = '';
+  for (const record of result.records) {
+    const row = document.createElement('tr');
+    row.className = record.severity;
+    cell(row, record.time);
+    cell(row, record.level);
+    cell(row, record.src + '(' + record.pid + ')');
+    cell(row, record.desc);
+    cell(row, record.uid);
+    body.appendChild(row);
+  }
+  const last = Math.min(offset + PAGE, result.total);
+  status.textContent = (result.total ? offset + 1 : 0) + ' - ' + last + ' of ' + result.total;
+}
+
+form.addEventListener('submit', (e) => { e.preventDefault(); offset = 0; load(); });
+document.getElementById('prev').addEventListener('click', () => { offset = Math.max(0, offset - PAGE); load(); });
+document.getElementById('next').addEventListener('click', () => { offset += PAGE; load(); });
+
+fetch('/api/sources').then((response) => response.json()).then((sources) => {
+  const select = document.getElementById('sources');
+  for (const source of sources) {
+    const option = document.createElement('option');
+    option.value = source.src;
+    option.textContent = source.src + ' (' + source.count + ')';
+    select.appendChild(option);
+  }
+});
+load();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_level(level).with_ts(ts).with_src(src).with_content(&format!("{} ", src), "\n")
+    }
+
+    fn server() -> LogServer {
+        LogServer::new(vec![
+            line("LOG_DEV_INFO", 3000, "SRC1", "third"),
+            line("LOG_DEV_ERROR", 1000, "SRC2", "first \"quoted\""),
+            line("LOG_DEV_WARNING", 2000, "SRC1", "second"),
+        ])
+    }
+
+    #[test]
+    fn url_decoding() {
+        assert!(decode_url("a%20b+c") == "a b c");
+        assert!(decode_url("100%") == "100%");
+        assert!(decode_url("%zz%3a") == "%zz:");
+
+        let params = parse_query("src=UI%2CRecon&text=&flag");
+        assert!(params["src"] == "UI,Recon" && params["text"].is_empty() && params.contains_key("flag"));
+    }
+
+    #[test]
+    fn json_escaping() {
+        assert!(escape_json("a\"b\\c\nd\x01") == "a\\\"b\\\\c\\nd\\u0001");
+    }
+
+    #[test]
+    fn it_works() {
+        let server = server();
+        let response = server.respond("GET", "/api/records?level=warning&limit=1");
+        assert!(response.status == "200 OK");
+        assert!(response.body.starts_with("{\"total\":2,\"offset\":0,\"records\":[{\"ts\":1000,\"time\":\"700101 00:00:01.000\",\"level\":\"DEV_ERROR\""));
+        assert!(response.body.contains("\"desc\":\"first \\\"quoted\\\"\""));
+        assert!(!response.body.contains("second"));
+
+        let response = server.respond("GET", "/api/records?src=SRC1&text=THIRD&from=700101+00:00:02");
+        assert!(response.body.starts_with("{\"total\":1,"));
+
+        assert!(server.respond("GET", "/api/records?level=loud").status == "400 Bad Request");
+        assert!(server.respond("GET", "/api/sources").body == "[{\"src\":\"SRC1\",\"count\":2},{\"src\":\"SRC2\",\"count\":1}]");
+        assert!(server.respond("GET", "/").body.starts_with("<!DOCTYPE html>"));
+        assert!(server.respond("GET", "/foo").status == "404 Not Found");
+        assert!(server.respond("POST", "/").status == "405 Method Not Allowed");
+    }
+
+    #[test]
+    fn request_reading() {
+        let request = read_request("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".as_bytes()).unwrap();
+        assert!(request.unwrap() == "GET / HTTP/1.1\r\n");
+
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_REQUEST_LENGTH as usize));
+        assert!(read_request(long_line.as_bytes()).unwrap().is_none());
+        let long_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "Header: value\r\n".repeat(MAX_REQUEST_LENGTH as usize / 10));
+        assert!(read_request(long_headers.as_bytes()).unwrap().is_none());
+    }
+}
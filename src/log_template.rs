@@ -0,0 +1,267 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+pub const DEFAULT_TEMPLATE_WINDOW: i64 = 10;    // in minutes
+const MINUTE_MS: i64 = 60 * 1000;
+
+// a template is reported as unusually frequent in a time window if it shows up
+// FREQUENT_FACTOR times more than its average, and at least MIN_FREQUENT_COUNT times
+const FREQUENT_FACTOR: usize = 5;
+const MIN_FREQUENT_COUNT: usize = 10;
+
+const GUID_LENGTH: usize = 36;
+const MIN_HEX_LENGTH: usize = 8;    // for hex values without the 0x prefix, e.g. hashes
+
+// Example: "open C:\data\1.dat for 0X2001 in 35 ms" => "open <PATH> for <HEX> in <NUM> ms"
+pub fn extract_template(desc: &str) -> String {
+    let mut template = String::with_capacity(desc.len());
+    for token in desc.split_whitespace() {
+        if !template.is_empty() {
+            template.push(' ');
+        }
+        // Example: uid=0X2001 => uid=<HEX>
+        for (i, token) in token.split('=').enumerate() {
+            if i > 0 {
+                template.push('=');
+            }
+            mask_token(token, &mut template);
+        }
+    }
+    template
+}
+
+fn mask_token(token: &str, template: &mut String) {
+    // keep the punctuation around the token, e.g. "(size" or "1024)."
+    let is_punctuation = |c: char| !c.is_alphanumeric() && c != '\\' && c != '/';
+    let core = token.trim_matches(is_punctuation);
+    if core.is_empty() {
+        template.push_str(token);
+        return;
+    }
+
+    let start = token.len() - token.trim_start_matches(is_punctuation).len();
+    template.push_str(&token[..start]);
+    if core.contains(['\\', '/']) {
+        template.push_str("<PATH>");
+    } else if is_guid(core) {
+        template.push_str("<GUID>");
+    } else if is_hex(core) {
+        template.push_str("<HEX>");
+    } else {
+        mask_numbers(core, template);
+    }
+    template.push_str(&token[start + core.len()..]);
+}
+
+// Example: 936DA01F-9ABD-4D9D-80C7-02AF85C822A8
+fn is_guid(token: &str) -> bool {
+    token.len() == GUID_LENGTH && token.char_indices().all(|(i, c)| match i {
+        8 | 13 | 18 | 23 => c == '-',
+        _ => c.is_ascii_hexdigit(),
+    })
+}
+
+// Example: 0X2001, 0xff, 3fa85f6457174562
+fn is_hex(token: &str) -> bool {
+    if token.len() > 2 && (token.starts_with("0x") || token.starts_with("0X")) {
+        return token[2..].chars().all(|c| c.is_ascii_hexdigit());
+    }
+    token.len() >= MIN_HEX_LENGTH
+        && token.chars().all(|c| c.is_ascii_hexdigit())
+        && token.chars().any(|c| c.is_ascii_digit())
+}
+
+// Example: step12 => step<NUM>, 3.14 => <NUM>
+fn mask_numbers(token: &str, template: &mut String) {
+    let mut in_number = false;
+    let mut chars = token.chars().peekable();
+    while let Some(c) = chars.next() {
+        let in_decimal = in_number && c == '.' && chars.peek().is_some_and(|c| c.is_ascii_digit());
+        if c.is_ascii_digit() || in_decimal {
+            if !in_number {
+                template.push_str("<NUM>");
+                in_number = true;
+            }
+        } else {
+            template.push(c);
+            in_number = false;
+        }
+    }
+}
+
+// FNV-1a, which keeps the template ids stable across runs and log folders
+pub fn template_id(template: &str) -> String {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in template.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    format!("T{:08x}", hash)
+}
+
+// Example: "... [FOO file.cpp 128] [0X2001]\n" => "... [FOO file.cpp 128] [0X2001] [T1a2b3c4d]\n"
+pub fn annotate_line(line: &mut LogLine) {
+    let id = template_id(&extract_template(line.desc()));
+    let end = line.content.trim_end_matches('\n').len();
+    line.content.insert_str(end, &format!(" [{}]", id));
+}
+
+struct Template {
+    id: String,
+    text: String,
+    count: usize,
+}
+
+pub struct TemplateMiner {
+    window: i64,                                        // in milliseconds
+    tz: Option<FixedOffset>,
+    templates: HashMap<String, Template>,               // template id => template
+    sources: BTreeMap<String, HashMap<String, usize>>,  // source => template id => count
+    windows: BTreeMap<i64, HashMap<String, usize>>,     // window index => template id => count
+}
+
+impl TemplateMiner {
+    pub fn new(window_minutes: i64) -> Self {
+        TemplateMiner {
+            window: window_minutes.max(1) * MINUTE_MS,
+            tz: None,
+            templates: HashMap::new(),
+            sources: BTreeMap::new(),
+            windows: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        self.tz.get_or_insert(line.tz);
+
+        let text = extract_template(line.desc());
+        let id = template_id(&text);
+        self.templates.entry(id.clone()).or_insert_with(|| Template { id: id.clone(), text, count: 0 }).count += 1;
+
+        let templates = self.sources.entry(line.src.clone()).or_default();
+        *templates.entry(id.clone()).or_insert(0) += 1;
+
+        if let Some(ts) = line.timestamp() {
+            let templates = self.windows.entry(ts.div_euclid(self.window)).or_default();
+            *templates.entry(id).or_insert(0) += 1;
+        }
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        writeln!(w, "total templates: {}, window: {} minutes", self.templates.len(), self.window / MINUTE_MS)?;
+
+        writeln!(w, "\n== templates per source ==")?;
+        for (src, templates) in &self.sources {
+            writeln!(w, "{}: {} templates", src, templates.len())?;
+            for (template, count) in self.sorted(templates) {
+                writeln!(w, "{:>8} {} {}", count, template.id, template.text)?;
+            }
+        }
+
+        writeln!(w, "\n== templates per window ==")?;
+        let window_count = match (self.windows.keys().next(), self.windows.keys().last()) {
+            (Some(first), Some(last)) => (last - first + 1) as usize,
+            _ => 1,
+        };
+        let mut seen: HashSet<&String> = HashSet::new();
+        for (i, (window, templates)) in self.windows.iter().enumerate() {
+            let records: usize = templates.values().sum();
+            let start = format_timestamp(window * self.window, &tz);
+            writeln!(w, "{} {} records, {} templates", &start[..start.len() - 7], records, templates.len())?;
+
+            for (template, count) in self.sorted(templates) {
+                let is_new = seen.insert(&template.id) && i > 0;
+                let average = template.count / window_count;
+                if is_new {
+                    writeln!(w, "    new      {:>8} {} {}", count, template.id, template.text)?;
+                } else if count >= MIN_FREQUENT_COUNT && count >= FREQUENT_FACTOR * average.max(1) {
+                    writeln!(w, "    frequent {:>8} {} {}", count, template.id, template.text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn sorted<'a>(&'a self, counts: &'a HashMap<String, usize>) -> Vec<(&'a Template, usize)> {
+        let mut templates: Vec<(&Template, usize)> = counts.iter().map(|(id, count)| (&self.templates[id], *count)).collect();
+        templates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.text.cmp(&b.0.text)));
+        templates
+    }
+}
+
+pub fn mine_folder(folder: &Path, filter: &LogFilter, window_minutes: i64, pool: &WorkerPool) -> io::Result<()> {
+    let mut miner = TemplateMiner::new(window_minutes);
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            miner.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    miner.report(&mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_tz(FixedOffset::east_opt(8 * 3600).unwrap()).with_src(src).with_content("DEV_INFO  ", "\n")
+    }
+
+    #[test]
+    fn template_extraction() {
+        assert!(extract_template("open C:\\data\\1.dat for 0X2001 in 35 ms") == "open <PATH> for <HEX> in <NUM> ms");
+        assert!(extract_template("uid={936DA01F-9ABD-4D9D-80C7-02AF85C822A8}.") == "uid={<GUID>}.");
+        assert!(extract_template("step12 took 3.14s, hash 3fa85f6457174562") == "step<NUM> took <NUM>s, hash <HEX>");
+        assert!(extract_template("file /var/log/x.log (size 1024)") == "file <PATH> (size <NUM>)");
+        assert!(extract_template("version 1.2.3 ready.") == "version <NUM> ready.");
+        assert!(extract_template("  spaces\tand -- dashes ") == "spaces and -- dashes");
+    }
+
+    #[test]
+    fn template_ids() {
+        assert!(template_id("foo") == template_id("foo"));
+        assert!(template_id("foo") != template_id("bar"));
+        assert!(template_id("").len() == 9);
+    }
+
+    #[test]
+    fn line_annotation() {
+        let mut l = line(0, "SRC", "value 1");
+        annotate_line(&mut l);
+        assert!(l.content == format!("DEV_INFO  value 1 [{}]\n", template_id("value <NUM>")));
+    }
+
+    #[test]
+    fn it_works() {
+        let mut miner = TemplateMiner::new(1);
+        miner.add(&line(1641013262865, "SRC1", "value 1"));
+        miner.add(&line(1641013262866, "SRC1", "value 2"));
+        miner.add(&line(1641013262867, "SRC2", "value 3"));
+        for i in 0..20 {
+            miner.add(&line(1641013262865 + 5 * MINUTE_MS, "SRC2", &format!("retry {}", i)));
+        }
+
+        assert!(miner.templates.len() == 2);
+        assert!(miner.sources["SRC1"].len() == 1);
+        assert!(miner.windows.len() == 2);
+
+        let mut report: Vec<u8> = Vec::new();
+        miner.report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains(&format!("       2 {} value <NUM>", template_id("value <NUM>"))));
+        assert!(report.contains(&format!("    new            20 {} retry <NUM>", template_id("retry <NUM>"))));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_level::LogSeverity;
+use crate::log_parser::{parse_time, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::collections::BTreeSet;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+// the minimum severities to cycle through
+const LEVELS: [Option<LogSeverity>; 4] = [None, Some(LogSeverity::Info), Some(LogSeverity::Warning), Some(LogSeverity::Error)];
+const HELP: &str = "q quit | arrows/PgUp/PgDn/Home/End scroll | / text | l level | t time | s sources | v view | left/right source";
+
+enum Input {
+    None,
+    Text(String),
+    Time(String),
+    Sources,
+}
+
+enum View {
+    Combined,
+    Source(usize),      // index of the source shown alone
+}
+
+// the state of the interactive viewer, kept apart from the terminal so that it could be tested
+pub struct LogViewer {
+    lines: Vec<LogLine>,        // merged by timestamp
+    src_ids: Vec<usize>,        // index of the source of each line
+    sources: Vec<String>,
+    enabled: Vec<bool>,         // sources shown in the combined view
+    level: usize,               // index into LEVELS
+    text: String,               // in lowercase, matched case-insensitively
+    view: View,
+    visible: Vec<usize>,        // indexes of the lines passing the filters
+    top: usize,                 // index into visible of the first line on the screen
+    height: usize,              // lines on the screen
+    input: Input,
+    selected: usize,            // index of the selected source in the source list
+    message: String,
+}
+
+impl LogViewer {
+    pub fn new(mut lines: Vec<LogLine>) -> Self {
+        lines.sort_by_key(|line| line.timestamp().unwrap_or(i64::MIN));    // stable, so the file order is kept for the same timestamp
+
+        let sources: Vec<String> = lines.iter().map(|line| line.src.clone()).collect::<BTreeSet<String>>().into_iter().collect();
+        let src_ids = lines.iter().map(|line| sources.binary_search(&line.src).unwrap()).collect();
+        let mut viewer = LogViewer {
+            lines,
+            src_ids,
+            enabled: vec![true; sources.len()],
+            sources,
+            level: 0,
+            text: String::new(),
+            view: View::Combined,
+            visible: Vec::new(),
+            top: 0,
+            height: 1,
+            input: Input::None,
+            selected: 0,
+            message: String::new(),
+        };
+        viewer.refresh();
+        viewer
+    }
+
+    fn accept(&self, i: usize) -> bool {
+        let line = &self.lines[i];
+        let src_accepted = match self.view {
+            View::Combined => self.enabled[self.src_ids[i]],
+            View::Source(src) => self.src_ids[i] == src,
+        };
+        src_accepted
+            && LEVELS[self.level].map_or(true, |severity| line.level.severity >= severity)
+            && (self.text.is_empty() || line.content.to_lowercase().contains(&self.text))
+    }
+
+    // keep the first line on the screen, or the one right after it if it's filtered out
+    fn refresh(&mut self) {
+        let first = self.visible.get(self.top).copied().unwrap_or(0);
+        self.visible = (0..self.lines.len()).filter(|i| self.accept(*i)).collect();
+        self.top = self.visible.partition_point(|i| *i < first);
+        self.scroll(0);
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let max_top = self.visible.len().saturating_sub(self.height);
+        self.top = (self.top as isize + delta).clamp(0, max_top as isize) as usize;
+    }
+
+    pub fn resize(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.scroll(0);
+    }
+
+    // to the first shown line at or after the time, in the timezone of each line
+    fn jump(&mut self, value: &str) {
+        let time = match parse_time(value) {
+            Some(time) => time,
+            None => {
+                self.message = format!("invalid time {}", value);
+                return;
+            }
+        };
+        let found = self.visible.iter().position(|i| {
+            let line = &self.lines[*i];
+            let ts = time.and_utc().timestamp_millis() - line.tz.local_minus_utc() as i64 * 1000;
+            line.timestamp().is_some_and(|line_ts| line_ts >= ts)
+        });
+        match found {
+            Some(top) => {
+                self.top = top;
+                self.scroll(0);
+            },
+            None => self.message = format!("no record after {}", value),
+        }
+    }
+
+    fn switch_source(&mut self, delta: isize) {
+        if let View::Source(src) = self.view {
+            let count = self.sources.len() as isize;
+            self.view = View::Source(((src as isize + delta).rem_euclid(count)) as usize);
+            self.refresh();
+        }
+    }
+
+    // return false to quit
+    pub fn handle(&mut self, key: KeyCode) -> bool {
+        self.message.clear();
+        match &mut self.input {
+            Input::Text(text) | Input::Time(text) => match key {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                },
+                KeyCode::Enter => {
+                    match std::mem::replace(&mut self.input, Input::None) {
+                        Input::Text(text) => {
+                            self.text = text.to_lowercase();
+                            self.refresh();
+                        },
+                        Input::Time(time) => self.jump(&time),
+                        _ => (),
+                    }
+                },
+                KeyCode::Esc => self.input = Input::None,
+                _ => (),
+            },
+            Input::Sources => match key {
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected = (self.selected + 1).min(self.sources.len().saturating_sub(1)),
+                KeyCode::Char(' ') if !self.sources.is_empty() => {
+                    self.enabled[self.selected] = !self.enabled[self.selected];
+                    self.refresh();
+                },
+                KeyCode::Char('a') => {
+                    let all = !self.enabled.iter().all(|enabled| *enabled);
+                    self.enabled.iter_mut().for_each(|enabled| *enabled = all);
+                    self.refresh();
+                },
+                KeyCode::Enter | KeyCode::Esc | KeyCode::Char('s') => self.input = Input::None,
+                _ => (),
+            },
+            Input::None => match key {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Up | KeyCode::Char('k') => self.scroll(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.scroll(1),
+                KeyCode::PageUp => self.scroll(-(self.height as isize)),
+                KeyCode::PageDown | KeyCode::Char(' ') => self.scroll(self.height as isize),
+                KeyCode::Home | KeyCode::Char('g') => self.scroll(-(self.visible.len() as isize)),
+                KeyCode::End | KeyCode::Char('G') => self.scroll(self.visible.len() as isize),
+                KeyCode::Char('/') => self.input = Input::Text(String::new()),
+                KeyCode::Char('t') => self.input = Input::Time(String::new()),
+                KeyCode::Char('s') => self.input = Input::Sources,
+                KeyCode::Char('l') => {
+                    self.level = (self.level + 1) % LEVELS.len();
+                    self.refresh();
+                },
+                KeyCode::Char('v') if !self.sources.is_empty() => {
+                    self.view = match self.view {
+                        View::Combined => View::Source(self.selected),
+                        View::Source(_) => View::Combined,
+                    };
+                    self.refresh();
+                },
+                KeyCode::Left => self.switch_source(-1),
+                KeyCode::Right | KeyCode::Tab => self.switch_source(1),
+                _ => (),
+            },
+        }
+        true
+    }
+
+    fn status(&self) -> String {
+        let view = match self.view {
+            View::Combined => "combined".to_string(),
+            View::Source(src) => format!("source {}", self.sources[src]),
+        };
+        let level = LEVELS[self.level].map_or("ALL", |severity| severity.as_str());
+        let position = match self.visible.is_empty() {
+            true => 0,
+            false => self.top + 1,
+        };
+        format!(" {} | {}/{} of {} records | level >= {} | text: {} | {}",
+            view, position, self.visible.len(), self.lines.len(), level, self.text, self.message)
+    }
+
+    fn render<W: Write>(&self, w: &mut W, width: usize) -> io::Result<()> {
+        let fit = |text: &str| -> String {
+            let text: String = text.chars().take(width).collect();
+            format!("{:<width$}", text, width = width)
+        };
+
+        queue!(w, cursor::MoveTo(0, 0), SetAttribute(Attribute::Reverse), Print(fit(&self.status())), SetAttribute(Attribute::Reset))?;
+        for row in 0..self.height {
+            queue!(w, cursor::MoveTo(0, row as u16 + 1), terminal::Clear(ClearType::CurrentLine))?;
+            if let Input::Sources = self.input {
+                let i = self.selected.saturating_sub(self.height - 1) + row;     // keep the selected one on the screen
+                if let Some(src) = self.sources.get(i) {
+                    let mark = if self.enabled[i] { "[x]" } else { "[ ]" };
+                    if i == self.selected {
+                        queue!(w, SetAttribute(Attribute::Reverse))?;
+                    }
+                    queue!(w, Print(fit(&format!("{} {}", mark, src))), SetAttribute(Attribute::Reset))?;
+                }
+                continue;
+            }
+
+            if let Some(i) = self.visible.get(self.top + row) {
+                let line = &self.lines[*i];
+                let color = match line.level.severity {
+                    LogSeverity::Error | LogSeverity::Fatal => Some(Color::Red),
+                    LogSeverity::Warning => Some(Color::Yellow),
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    queue!(w, SetForegroundColor(color))?;
+                }
+                queue!(w, Print(fit(line.content.trim_end())), ResetColor)?;
+            }
+        }
+
+        let prompt = match &self.input {
+            Input::None => HELP.to_string(),
+            Input::Text(text) => format!("text: {}", text),
+            Input::Time(time) => format!("jump to (e.g. 220101 13:01:02): {}", time),
+            Input::Sources => "up/down select | space toggle | a all | enter close".to_string(),
+        };
+        queue!(w, cursor::MoveTo(0, self.height as u16 + 1), SetAttribute(Attribute::Reverse), Print(fit(&prompt)), SetAttribute(Attribute::Reset))?;
+        w.flush()
+    }
+}
+
+// restore the terminal even if the viewer fails
+struct RawTerminal {
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal {})
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn view_folder(folder: &Path, filter: &LogFilter, pool: &WorkerPool) -> io::Result<()> {
+    let mut lines: Vec<LogLine> = Vec::new();
+    parse_folder_with(folder, pool, |parsed| {
+        lines.extend(parsed.into_iter().filter(|line| filter.accept(line)));
+        Ok(())
+    })?;
+    let mut viewer = LogViewer::new(lines);
+
+    let _terminal = RawTerminal::enter()?;
+    let mut stdout = io::stdout();
+    loop {
+        let (width, height) = terminal::size()?;
+        viewer.resize((height as usize).saturating_sub(2));    // for the status and the help lines
+        viewer.render(&mut stdout, width as usize)?;
+
+        // the others like resizing only need rendering again
+        if let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) = event::read()? {
+            if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+                break;
+            }
+            if !viewer.handle(code) {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str, ts: i64, src: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_level(level).with_ts(ts).with_src(src).with_content(&format!("{} ", src), "\n")
+    }
+
+    fn shown(viewer: &LogViewer) -> Vec<&str> {
+        viewer.visible.iter().map(|i| viewer.lines[*i].desc()).collect()
+    }
+
+    fn type_text(viewer: &mut LogViewer, text: &str) {
+        for c in text.chars() {
+            viewer.handle(KeyCode::Char(c));
+        }
+        viewer.handle(KeyCode::Enter);
+    }
+
+    fn viewer() -> LogViewer {
+        LogViewer::new(vec![
+            line("LOG_DEV_INFO", 3000, "SRC1", "third"),
+            line("LOG_DEV_ERROR", 1000, "SRC2", "first"),
+            line("LOG_DEV_WARNING", 2000, "SRC1", "second"),
+            line("LOG_DEV_INFO", 4000, "SRC2", "Fourth"),
+        ])
+    }
+
+    #[test]
+    fn filtering() {
+        let mut viewer = viewer();
+        assert!(shown(&viewer) == vec!["first", "second", "third", "Fourth"]);
+
+        viewer.handle(KeyCode::Char('l'));
+        viewer.handle(KeyCode::Char('l'));
+        assert!(shown(&viewer) == vec!["first", "second"]);
+        viewer.handle(KeyCode::Char('l'));
+        viewer.handle(KeyCode::Char('l'));
+
+        viewer.handle(KeyCode::Char('/'));
+        type_text(&mut viewer, "FOUR");
+        assert!(shown(&viewer) == vec!["Fourth"]);
+        viewer.handle(KeyCode::Char('/'));
+        type_text(&mut viewer, "");
+        assert!(shown(&viewer).len() == 4);
+
+        // toggle the first source off, then switch to the view of the second source only
+        viewer.handle(KeyCode::Char('s'));
+        viewer.handle(KeyCode::Char(' '));
+        viewer.handle(KeyCode::Enter);
+        assert!(shown(&viewer) == vec!["first", "Fourth"]);
+
+        viewer.handle(KeyCode::Char('v'));
+        assert!(shown(&viewer) == vec!["second", "third"]);
+        viewer.handle(KeyCode::Right);
+        assert!(shown(&viewer) == vec!["first", "Fourth"]);
+        assert!(viewer.status().starts_with(" source SRC2 | 2/2 of 4 records"));    // still around the same time
+        viewer.handle(KeyCode::Char('v'));
+        assert!(shown(&viewer) == vec!["first", "Fourth"]);
+
+        assert!(!viewer.handle(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut viewer = viewer();
+        viewer.resize(2);
+        viewer.handle(KeyCode::End);
+        assert!(viewer.top == 2);
+        viewer.handle(KeyCode::Down);
+        assert!(viewer.top == 2);
+        viewer.handle(KeyCode::PageUp);
+        assert!(viewer.top == 0);
+
+        viewer.handle(KeyCode::Char('t'));
+        type_text(&mut viewer, "700101 00:00:01.500");
+        assert!(viewer.top == 1);
+        viewer.handle(KeyCode::Char('t'));
+        type_text(&mut viewer, "700101 00:01:00");
+        assert!(viewer.top == 1 && viewer.message == "no record after 700101 00:01:00");
+
+        // the first line on the screen is kept when possible
+        viewer.handle(KeyCode::Char('l'));
+        viewer.handle(KeyCode::Char('l'));
+        assert!(viewer.top == 0 && shown(&viewer) == vec!["first", "second"]);
+    }
+}
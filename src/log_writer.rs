@@ -0,0 +1,195 @@
+use crate::log_parser::{LogField, FIELD_DELIM, HEADER_END, LOGGING_END};
+
+use chrono::FixedOffset;
+use std::io;
+use std::io::Write;
+
+// a record in the original format, the counterpart of LogLine for writing .uihlog files
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    pub level: String,          // e.g. "LOG_DEV_INFO"
+    pub local_ts: i64,          // in milliseconds since the Unix epoch
+    pub src: String,
+    pub pid: String,
+    pub tid: String,
+    pub file: String,
+    pub line: u32,
+    pub function: String,
+    pub uid: String,
+    pub desc: String,
+    pub server_ts: i64,         // in milliseconds since the Unix epoch
+}
+
+impl LogRecord {
+    // the original fields of a record (with or without its terminator), to write it out again with LogWriter
+    pub fn parse(record: &str) -> Option<LogRecord> {
+        let record = record.strip_suffix(LOGGING_END).unwrap_or(record);
+        let fields: Vec<&str> = record.split(FIELD_DELIM).collect();
+        if fields.len() < LogField::FieldCount.into() {
+            return None;
+        }
+
+        let server_ts = fields.len() - 1;   // in case there are unexpected field delimiters in the log description
+        let (src, pid_tid) = fields[LogField::SrcPidTid as usize].split_once('(')?;
+        let (pid, tid) = pid_tid.strip_suffix(')')?.split_once(':')?;
+        Some(LogRecord {
+            level: fields[LogField::Level as usize].to_string(),
+            local_ts: fields[LogField::LocalTS as usize].parse::<i64>().ok()?,
+            src: src.to_string(),
+            pid: pid.to_string(),
+            tid: tid.to_string(),
+            file: fields[LogField::FileName as usize].to_string(),
+            line: fields[LogField::LineNo as usize].parse::<u32>().ok()?,
+            function: fields[LogField::Function as usize].to_string(),
+            uid: fields[LogField::Uid as usize].to_string(),
+            desc: fields[LogField::Description as usize .. server_ts].join(&FIELD_DELIM.to_string()),
+            server_ts: fields[server_ts].parse::<i64>().ok()?,
+        })
+    }
+}
+
+// Example: "(UTC+08:00)", "(UTC-07:30)"
+pub fn format_timezone(tz: &FixedOffset) -> String {
+    let offset = tz.local_minus_utc();
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs() / 60;
+    format!("(UTC{}{:02}:{:02})", sign, offset / 60, offset % 60)
+}
+
+// the control characters of the format would break the record apart, so they are replaced by spaces
+fn write_field<W: Write>(w: &mut W, field: &str) -> io::Result<()> {
+    if field.contains(['\x01', '\x02', '\x03']) {
+        w.write_all(field.replace(['\x01', '\x02', '\x03'], " ").as_bytes())
+    } else {
+        w.write_all(field.as_bytes())
+    }
+}
+
+// the source, PID and TID are written as "src(pid:tid)", so the delimiters of that field are replaced by underscores
+fn escape_src_pid_tid(part: &str) -> String {
+    part.replace(['(', ':', ')'], "_")
+}
+
+// encode the records into the raw .uihlog format, which LogParser and the other UIH tools read
+pub struct LogWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(w: W) -> Self {
+        LogWriter { w }
+    }
+
+    // the timezone is appended unless the header (e.g. copied from another log file) has one already
+    pub fn write_header(&mut self, header: &str, tz: &FixedOffset) -> io::Result<()> {
+        self.w.write_all(header.replace('\x03', " ").as_bytes())?;     // only the end of text ends the header
+        if !header.contains("(UTC") {
+            write!(self.w, " {}", format_timezone(tz))?;
+        }
+        self.w.write_all(HEADER_END.as_bytes())
+    }
+
+    pub fn write(&mut self, record: &LogRecord) -> io::Result<()> {
+        let src_pid_tid = format!("{}({}:{})",
+            escape_src_pid_tid(&record.src), escape_src_pid_tid(&record.pid), escape_src_pid_tid(&record.tid));
+        let line = record.line.to_string();
+        let local_ts = record.local_ts.to_string();
+        let server_ts = record.server_ts.to_string();
+        let fields = [&record.level, &local_ts, &src_pid_tid, &record.file, &line, &record.function, &record.uid, &record.desc, &server_ts];
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(self.w, "{}", FIELD_DELIM)?;
+            }
+            write_field(&mut self.w, field)?;
+        }
+        self.w.write_all(LOGGING_END.as_bytes())
+    }
+
+    // a record copied as is from another log file, including its terminator
+    pub fn write_raw(&mut self, record: &[u8]) -> io::Result<()> {
+        self.w.write_all(record)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_level::LogLevel;
+    use crate::log_parser::LogParser;
+
+    fn record(ts: i64, src: &str, desc: &str) -> LogRecord {
+        LogRecord {
+            level: "LOG_DEV_WARNING".into(),
+            local_ts: ts - 5,
+            src: src.into(),
+            pid: "1001".into(),
+            tid: "7".into(),
+            file: "scan.cpp".into(),
+            line: 42,
+            function: "Scan".into(),
+            uid: "0X2001".into(),
+            desc: desc.into(),
+            server_ts: ts,
+        }
+    }
+
+    #[test]
+    fn timezone_formatting() {
+        assert!(format_timezone(&FixedOffset::east_opt(8 * 3600).unwrap()) == "(UTC+08:00)");
+        assert!(format_timezone(&FixedOffset::west_opt(7 * 3600 + 30 * 60).unwrap()) == "(UTC-07:30)");
+        assert!(format_timezone(&FixedOffset::east_opt(0).unwrap()) == "(UTC+00:00)");
+    }
+
+    #[test]
+    fn record_parsing() {
+        let fields: Vec<&str> = vec!["LOG_SVC_ERROR", "1641013262860", "Recon(1001:7)", "recon.cpp", "128", "Run", "0X2001", "DESC", "EXTRA", "1641013262865"];
+        let record = LogRecord::parse(&(fields.join(FIELD_DELIM.to_string().as_str()) + LOGGING_END)).unwrap();
+        assert!(record.level == "LOG_SVC_ERROR");
+        assert!(record.local_ts == 1641013262860 && record.server_ts == 1641013262865);
+        assert!(record.src == "Recon" && record.pid == "1001" && record.tid == "7");
+        assert!(record.file == "recon.cpp" && record.line == 128 && record.function == "Run");
+        assert!(record.desc == "DESC\x02EXTRA");
+
+        assert!(LogRecord::parse("LOG_SVC_ERROR\x02BAD_TS").is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let tz = FixedOffset::west_opt(7 * 3600 + 30 * 60).unwrap();
+        let records = vec![
+            record(1641013262865, "ScanSrv", "scan started"),
+            record(1641013262866, "Recon", "LOG\x01\nLOG injected\x02delimiters"),
+            record(1641013263000, "UI", "multi\nline"),
+            LogRecord { pid: "10:01".into(), tid: "(7)".into(), ..record(1641013263001, "Scan(Srv)", "delimiters in the source") },
+        ];
+
+        let mut data: Vec<u8> = Vec::new();
+        let mut writer = LogWriter::new(&mut data);
+        writer.write_header("synthetic log", &tz).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let lines = LogParser::new().parse_sync(String::from_utf8(data).unwrap());
+        assert!(lines.len() == records.len());
+        for (line, record) in lines.iter().zip(records.iter()) {
+            assert!(line.tz == tz);
+            assert!(line.src == escape_src_pid_tid(&record.src));
+            assert!(line.pid == escape_src_pid_tid(&record.pid));
+            assert!(line.uid == record.uid);
+            assert!(line.local_ts == Some(record.local_ts));
+            assert!(line.server_ts == Some(record.server_ts));
+            assert!(line.level == LogLevel::parse(&record.level));
+        }
+        assert!(lines[0].content == "DEV_WARN  211231 21:31:02.865 [211231 21:31:02.860] ScanSrv(1001:7) scan started [Scan scan.cpp 42] [0X2001]\n");
+        assert!(lines[1].desc() == "LOG  LOG injected delimiters");
+        assert!(lines[2].desc() == "multi line");
+        assert!(lines[3].src == "Scan_Srv_" && lines[3].pid == "10_01");
+        assert!(lines[3].content.contains(" Scan_Srv_(10_01:_7_) delimiters in the source "));
+    }
+}
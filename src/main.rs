@@ -1,48 +1,154 @@
 mod buffered_output;
+mod clock_skew;
+mod console_output;
+mod duplicate_collapser;
+mod error_bursts;
 mod file_parser;
 mod file_system;
+mod folder_diff;
 mod folder_parser;
+mod folder_stats;
+mod gap_detector;
+mod html_report;
+mod line_preparer;
+mod log_archive;
+mod log_cat;
+mod log_extract;
+mod log_filter;
+mod log_follower;
+mod log_index;
+mod log_level;
 mod log_parser;
+mod log_redactor;
+mod log_server;
+mod log_template;
+mod log_viewer;
+mod log_writer;
+mod options;
+mod parse_state;
+mod process_lifecycle;
 mod sorted_file_list;
+mod uid_correlator;
+mod worker_pool;
 
+use clock_skew::{analyze_folder, DEFAULT_SKEW_THRESHOLD};
+use error_bursts::{detect_bursts, DEFAULT_BURST_WINDOW};
+use folder_diff::diff_folders;
 use folder_parser::parse_folder;
-use file_parser::parse_file;
+use folder_stats::stats_folder;
+use gap_detector::{detect_gaps, DEFAULT_GAP_THRESHOLD};
+use file_parser::{parse_file, parse_stdin, STDIN_PATH};
+use html_report::report_folder;
+use log_archive::is_archive;
+use log_cat::cat_folder;
+use log_extract::extract_folder;
+use log_redactor::LogRedactor;
+use log_follower::follow_folder;
+use log_index::{index_folder, query_folder};
+use log_server::{serve_folder, DEFAULT_PORT};
+use log_template::{mine_folder, DEFAULT_TEMPLATE_WINDOW};
+use log_viewer::view_folder;
+use options::{Command, Options};
+use process_lifecycle::track_processes;
+use uid_correlator::correlate_folder;
+use worker_pool::WorkerPool;
 
 use std::env;
+use std::io;
 use std::path::Path;
+use std::process;
 use std::time::SystemTime;
 
-fn need_pid_output() -> bool {
-    match env::args().nth(2) {
-        Some(pid_output) => match pid_output.as_str() {
-            "1" => true,
-            _ => false,
+// the status goes to stderr, so stdout only carries the outputs of the commands
+// a broken pipe is not a failure, the reader has just seen enough, e.g. "uihlog cat logs | head"
+// false if the command failed, for the exit status
+fn check(result: io::Result<()>, action: &str) -> bool {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => true,
+        Err(e) => {
+            eprintln!("failed to {}: {}", action, e);
+            false
         },
-        None => false,
+        Ok(_) => true,
     }
 }
 
 fn main() {
-    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
-    println!("uihlog reloaded in Rust v{}", VERSION);
+    const VERSION: &str = env!("CARGO_PKG_VERSION");
+    eprintln!("uihlog reloaded in Rust v{}", VERSION);
 
-    let path = match env::args().nth(1) {
-        Some(path) => Path::new(&path).to_owned(),
-        None => env::current_dir().unwrap(),
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("invalid arguments: {}", e);
+            process::exit(1);
+        }
     };
 
-    let start = SystemTime::now();
-    if path.is_dir() {
-        if let Err(e) = parse_folder(&path, need_pid_output()) {
-            println!("failed to parse the folder: {}", e.to_string());
-        }
-    } else if path.is_file() {
-        if let Err(e) = parse_file(&path) {
-            println!("failed to parse the file: {}", e.to_string());
-        }
+    let mut pool = WorkerPool::new(options.threads);
+    if let Some(max_memory) = options.max_memory {
+        pool.set_memory_limit(max_memory);
     }
-    println!(
+
+    let path = &options.path;
+    let start = SystemTime::now();
+    let succeeded = match options.command {
+        Command::Stats => check(stats_folder(path, &options.filter, options.top, &pool), "report statistics of the folder"),
+        Command::Templates => {
+            let window = options.window.unwrap_or(DEFAULT_TEMPLATE_WINDOW);
+            check(mine_folder(path, &options.filter, window, &pool), "report templates of the folder")
+        },
+        Command::Skew => {
+            let threshold = options.threshold.unwrap_or(DEFAULT_SKEW_THRESHOLD);
+            check(analyze_folder(path, &options.filter, threshold, &pool), "analyze clock skew of the folder")
+        },
+        Command::Gaps => {
+            let threshold = options.threshold.unwrap_or(DEFAULT_GAP_THRESHOLD);
+            check(detect_gaps(path, &options.filter, threshold, &pool), "detect gaps of the folder")
+        },
+        Command::Pids => check(track_processes(path, &options.filter, &pool), "track processes of the folder"),
+        Command::Uids => check(correlate_folder(path, &options.output_folder(), &options.filter, &pool), "correlate records of the folder by uid"),
+        Command::Diff => {
+            let other_path = options.other_path.as_ref().unwrap();   // guaranteed by the option parsing
+            check(diff_folders(path, other_path, &options.filter, &pool), "compare the folders")
+        },
+        Command::Bursts => {
+            let window = options.window.unwrap_or(DEFAULT_BURST_WINDOW);
+            check(detect_bursts(path, &options.filter, window, options.top, &pool), "detect error bursts of the folder")
+        },
+        Command::Index => check(index_folder(path), "index the folder"),
+        Command::Query => check(query_folder(path, &options.filter, options.color), "query the folder"),
+        Command::View => check(view_folder(path, &options.filter, &pool), "view the folder"),
+        Command::Serve => {
+            let port = options.port.unwrap_or(DEFAULT_PORT);
+            check(serve_folder(path, &options.filter, port, &pool), "serve the folder")
+        },
+        Command::Report => check(report_folder(path, &options.output_folder(), &options.filter, options.top, &pool), "write the report of the folder"),
+        Command::Cat => check(cat_folder(path, &options, &pool), "print the folder"),
+        Command::Extract => {
+            let mut redactor = LogRedactor::new(options.redact, &options.redact_patterns);
+            check(extract_folder(path, &options.output_folder(), &options.filter, &mut redactor), "extract the folder")
+        },
+        Command::Split => {
+            if options.follow {
+                check(follow_folder(path, &options), "follow the folder")
+            } else if path == Path::new(STDIN_PATH) {
+                check(parse_stdin(&options), "parse stdin")
+            } else if path.is_dir() || (path.is_file() && is_archive(path)) {
+                check(parse_folder(path, &options, &pool), "parse the folder")
+            } else if path.is_file() {
+                check(parse_file(path, &options), "parse the file")
+            } else {
+                eprintln!("no such file or folder {:?}", path);
+                false
+            }
+        },
+    };
+    eprintln!(
         "total cost: {:?}",
         SystemTime::now().duration_since(start).unwrap()
     );
+    if !succeeded {
+        process::exit(1);
+    }
 }
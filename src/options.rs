@@ -0,0 +1,530 @@
+use crate::buffered_output::OutputCompression;
+use crate::console_output::ColorMode;
+use crate::file_parser::STDIN_PATH;
+use crate::log_archive::is_archive;
+use crate::log_filter::LogFilter;
+use crate::log_level::{LogCategory, LogSeverity};
+use crate::log_parser::parse_time;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+use std::env;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_TOP_COUNT: usize = 10;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Split,      // split the log files by source (and PID optionally), the default command
+    Stats,      // report statistics of the log folder without writing any file
+    Templates,  // group the descriptions into templates and report them per source and time window
+    Skew,       // analyze the clock skew between the local and server timestamps
+    Gaps,       // detect the periods of silence per source and PID
+    Pids,       // report every PID each source ran under, to reveal the restarts
+    Uids,       // group the records by uid across all the sources and PIDs
+    Diff,       // compare the message templates of two log folders
+    Bursts,     // rank the time windows in which the error rates spike
+    Index,      // build or update the index of record offsets, timestamps and sources per log file
+    Query,      // print the records matching the filter, seeking by the index
+    View,       // browse the merged timeline of the log folder interactively
+    Serve,      // serve the merged timeline of the log folder to the web browsers on localhost
+    Report,     // write a self-contained HTML report of the log folder
+    Cat,        // write the parsed lines to stdout, for the pagers and the shell pipelines
+    Extract,    // write the filtered records into a new log folder in the original format
+}
+
+impl Command {
+    fn parse(arg: &str) -> Option<Command> {
+        match arg {
+            "stats" => Some(Command::Stats),
+            "templates" => Some(Command::Templates),
+            "skew" => Some(Command::Skew),
+            "gaps" => Some(Command::Gaps),
+            "pids" => Some(Command::Pids),
+            "uids" => Some(Command::Uids),
+            "diff" => Some(Command::Diff),
+            "bursts" => Some(Command::Bursts),
+            "index" => Some(Command::Index),
+            "query" => Some(Command::Query),
+            "view" => Some(Command::View),
+            "serve" => Some(Command::Serve),
+            "report" => Some(Command::Report),
+            "cat" => Some(Command::Cat),
+            "extract" => Some(Command::Extract),
+            _ => None,
+        }
+    }
+}
+
+pub struct Options {
+    pub command: Command,
+    pub path: PathBuf,
+    pub other_path: Option<PathBuf>,    // the second folder to compare with, only for the diff command
+    pub pid_output: bool,
+    pub filter: LogFilter,
+    pub top: usize,
+    pub window: Option<i64>,    // in minutes, the default one depends on the command
+    pub template_id: bool,      // annotate each parsed line with its template id
+    pub collapse_src: bool,     // collapse consecutive duplicate records in the output by source
+    pub collapse_pid: bool,     // collapse consecutive duplicate records in the output by PID
+    pub threshold: Option<i64>, // in milliseconds, the default one depends on the command
+    pub fix_skew: bool,         // correct the local timestamps by the estimated clock skew
+    pub follow: bool,           // keep watching the newest log file in the folder
+    pub split_output: bool,     // write the split outputs instead of stdout in follow mode
+    pub incremental: bool,      // only parse the new files and the appended data since the last run
+    pub port: Option<u16>,      // for the serve command
+    pub color: ColorMode,       // for the lines printed to stdout
+    pub merge: bool,            // print the lines of all the files in timestamp order, for the cat command
+    pub output: Option<PathBuf>,    // the folder of the output files, instead of the log folder
+    pub compression: OutputCompression, // for the split outputs
+    pub redact: bool,           // replace the IPs, MACs, DICOM UIDs, dates of birth and user names in the outputs
+    pub redact_patterns: Vec<Regex>,    // the values to redact besides the built-in ones
+    pub threads: Option<usize>, // the worker threads to read and parse the log files, the available cores by default
+    pub max_memory: Option<usize>,  // in bytes, for the log files being read and parsed by the worker threads
+}
+
+impl Options {
+    // Example: uihlog [command] [--pid] [--min-level warning] [--category dev,svc] <path> [1]
+    // the trailing "1" is kept for the batch scripts under utils, which enables the PID output
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut args = args.peekable();
+        let command = match args.peek().and_then(|arg| Command::parse(arg)) {
+            Some(command) => {
+                args.next();
+                command
+            },
+            None => Command::Split,
+        };
+
+        let mut options = Options {
+            command,
+            path: PathBuf::new(),
+            other_path: None,
+            pid_output: false,
+            filter: LogFilter::new(),
+            top: DEFAULT_TOP_COUNT,
+            window: None,
+            threshold: None,
+            template_id: false,
+            collapse_src: false,
+            collapse_pid: false,
+            fix_skew: false,
+            follow: false,
+            split_output: false,
+            incremental: false,
+            port: None,
+            color: ColorMode::Auto,
+            merge: false,
+            output: None,
+            compression: OutputCompression::None,
+            redact: false,
+            redact_patterns: Vec::new(),
+            threads: None,
+            max_memory: None,
+        };
+
+        let mut path: Option<PathBuf> = None;
+        let mut pid_option = false;     // unlike the legacy trailing "1", which is ignored for a single file as before
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--pid" => {
+                    options.pid_output = true;
+                    pid_option = true;
+                },
+                "--template-id" => options.template_id = true,
+                "--fix-skew" => options.fix_skew = true,
+                "--follow" => options.follow = true,
+                "--split" => options.split_output = true,
+                "--incremental" => options.incremental = true,
+                "--merge" => options.merge = true,
+                "--redact" => options.redact = true,
+                "--redact-pattern" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.redact_patterns.push(Regex::new(&value).map_err(|_| format!("invalid pattern {}", value))?);
+                },
+                "--collapse" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    for stream in value.split(',') {
+                        match stream.trim() {
+                            "src" => options.collapse_src = true,
+                            "pid" => options.collapse_pid = true,
+                            "all" => {
+                                options.collapse_src = true;
+                                options.collapse_pid = true;
+                            },
+                            _ => return Err(format!("invalid stream {}", stream)),
+                        }
+                    }
+                },
+                "--window" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.window = match value.parse::<i64>() {
+                        // in milliseconds when used, which must not overflow
+                        Ok(window) if window > 0 && window.checked_mul(60 * 1000).is_some() => Some(window),
+                        _ => return Err(format!("invalid window {}", value)),
+                    };
+                },
+                "--threshold" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.threshold = Some(Self::parse_duration(&value)?);
+                },
+                "--color" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.color = ColorMode::parse(&value).ok_or(format!("invalid color mode {}", value))?;
+                },
+                "--compress" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.compression = OutputCompression::parse(&value).ok_or(format!("invalid compression {}", value))?;
+                },
+                "--threads" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.threads = match value.parse::<usize>() {
+                        Ok(threads) if threads > 0 => Some(threads),
+                        _ => return Err(format!("invalid thread count {}", value)),
+                    };
+                },
+                "--max-memory" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    // in MB
+                    options.max_memory = match value.parse::<usize>().ok().filter(|mb| *mb > 0).and_then(|mb| mb.checked_mul(1024 * 1024)) {
+                        Some(max_memory) => Some(max_memory),
+                        None => return Err(format!("invalid memory size {}", value)),
+                    };
+                },
+                "--port" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.port = Some(value.parse::<u16>().map_err(|_| format!("invalid port {}", value))?);
+                },
+                "--output" => options.output = Some(PathBuf::from(Self::next_value(&mut args, &arg)?)),
+                "--top" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.top = value.parse::<usize>().map_err(|_| format!("invalid count {}", value))?;
+                },
+                "--min-level" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.filter.set_min_severity(Self::parse_severity(&value)?);
+                },
+                "--category" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    for category in value.split(',') {
+                        options.filter.add_category(Self::parse_category(category)?);
+                    }
+                },
+                "--src" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    for src in value.split(',') {
+                        options.filter.add_source(src.trim());
+                    }
+                },
+                "--from" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.filter.set_from(Self::parse_time(&value)?);
+                },
+                "--to" => {
+                    let value = Self::next_value(&mut args, &arg)?;
+                    options.filter.set_to(Self::parse_time(&value)?);
+                },
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => match path {
+                    None => path = Some(PathBuf::from(arg)),
+                    Some(_) if options.command == Command::Diff && options.other_path.is_none() => {
+                        options.other_path = Some(PathBuf::from(arg));
+                    },
+                    Some(_) if arg == "1" => options.pid_output = true,
+                    // the other trailing arguments of the legacy command line were always ignored, e.g. "0" of the older batch scripts
+                    Some(_) if options.command == Command::Split => eprintln!("ignored argument {}", arg),
+                    Some(_) => return Err(format!("unexpected argument {}", arg)),
+                },
+            }
+        }
+
+        options.path = match path {
+            Some(path) => path,
+            None => env::current_dir().map_err(|e| e.to_string())?,
+        };
+        if options.command == Command::Diff && options.other_path.is_none() {
+            return Err("two folders are required to compare".into());
+        }
+        if options.follow && options.command != Command::Split {
+            return Err("follow mode is only supported for splitting".into());
+        }
+        if (options.collapse_src || options.collapse_pid) && options.follow {
+            return Err("collapsing is not supported in follow mode, the runs are never finished".into());
+        }
+        if options.incremental && options.follow {
+            return Err("incremental parsing is not supported in follow mode".into());
+        }
+        if options.compression != OutputCompression::None && options.follow {
+            return Err("compression is not supported in follow mode, the compressed streams are never finished".into());
+        }
+        // a single file or stdin is parsed into one output, which isn't split by source or PID
+        let single_file = options.path == Path::new(STDIN_PATH) || (options.path.is_file() && !is_archive(&options.path));
+        if options.compression != OutputCompression::None && options.command == Command::Split && single_file {
+            return Err("compression is only supported for splitting a folder or an archive".into());
+        }
+        if (pid_option || options.collapse_src || options.collapse_pid) && options.command == Command::Split
+            && !options.follow && single_file {
+            return Err("pid output and collapsing are only supported for splitting a folder or an archive".into());
+        }
+        if options.incremental && is_archive(&options.path) {
+            return Err("incremental parsing is not supported for archives".into());
+        }
+        if options.command == Command::Extract && options.output.is_none() {
+            return Err("an output folder is required to extract".into());
+        }
+        let redact = options.redact || !options.redact_patterns.is_empty();
+        if redact && ![Command::Split, Command::Cat, Command::Extract].contains(&options.command) {
+            return Err("redaction is only supported for splitting, cat and extract".into());
+        }
+        if redact && options.incremental {
+            return Err("redaction is not supported for incremental parsing, the pseudonyms are not kept between runs".into());
+        }
+        if options.merge && options.command != Command::Cat {
+            return Err("merged order is only supported for the cat command".into());
+        }
+        Ok(options)
+    }
+
+    // the options which change the split outputs, the outputs of different options shouldn't be mixed
+    // the state is kept in the output folder, which other log folders could be split into as well
+    pub fn output_signature(&self) -> String {
+        format!("input={} pid={} template-id={} collapse-src={} collapse-pid={} fix-skew={} compress={} {}",
+            self.path.display(), self.pid_output, self.template_id, self.collapse_src, self.collapse_pid, self.fix_skew,
+            self.compression.as_str(), self.filter)
+    }
+
+    // the output directory if given, otherwise next to the archive, or in the log folder
+    pub fn output_folder(&self) -> PathBuf {
+        match &self.output {
+            Some(output) => output.clone(),
+            None if is_archive(&self.path) => self.path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => self.path.clone(),
+        }
+    }
+
+    fn next_value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+        args.next().ok_or(format!("missing value for option {}", option))
+    }
+
+    // Example: 500ms, 30s, 5m, 1h, or 1000 (in milliseconds)
+    fn parse_duration(value: &str) -> Result<i64, String> {
+        let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+            Some(idx) => value.split_at(idx),
+            None => (value, "ms"),
+        };
+        let unit = match unit {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            _ => return Err(format!("invalid duration {}", value)),
+        };
+        match number.parse::<i64>().ok().filter(|number| *number > 0).and_then(|number| number.checked_mul(unit)) {
+            Some(duration) => Ok(duration),
+            None => Err(format!("invalid duration {}", value)),
+        }
+    }
+
+    fn parse_time(value: &str) -> Result<NaiveDateTime, String> {
+        parse_time(value).ok_or(format!("invalid time {}", value))
+    }
+
+    fn parse_severity(value: &str) -> Result<LogSeverity, String> {
+        match LogSeverity::parse(value) {
+            LogSeverity::Unknown => Err(format!("invalid level {}", value)),
+            severity => Ok(severity),
+        }
+    }
+
+    fn parse_category(value: &str) -> Result<LogCategory, String> {
+        match LogCategory::parse(value.trim()) {
+            LogCategory::Unknown => Err(format!("invalid category {}", value)),
+            category => Ok(category),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn legacy_arguments() {
+        let options = parse(&["logs", "1"]).unwrap();
+        assert!(options.path == Path::new("logs"));
+        assert!(options.pid_output);
+        assert!(options.filter.is_empty());
+
+        let options = parse(&["logs"]).unwrap();
+        assert!(options.command == Command::Split);
+        assert!(!options.pid_output);
+
+        let options = parse(&["logs", "0", "extra"]).unwrap();
+        assert!(options.path == Path::new("logs") && !options.pid_output);
+    }
+
+    #[test]
+    fn commands() {
+        let options = parse(&["stats", "--top", "5", "logs"]).unwrap();
+        assert!(options.command == Command::Stats);
+        assert!(options.path == Path::new("logs"));
+        assert!(options.top == 5);
+
+        assert!(parse(&["stats", "--top", "many"]).is_err());
+
+        let options = parse(&["templates", "--window", "5", "logs"]).unwrap();
+        assert!(options.command == Command::Templates);
+        assert!(options.window == Some(5));
+
+        assert!(parse(&["templates", "--window", "0"]).is_err());
+        assert!(parse(&["bursts", "--window", "999999999999999999", "logs"]).is_err());
+
+        let options = parse(&["skew", "--threshold", "2s", "logs"]).unwrap();
+        assert!(options.command == Command::Skew);
+        assert!(options.threshold == Some(2000));
+
+        let options = parse(&["gaps", "logs"]).unwrap();
+        assert!(options.command == Command::Gaps);
+        assert!(options.threshold.is_none());
+
+        assert!(parse(&["pids", "logs"]).unwrap().command == Command::Pids);
+        assert!(parse(&["uids", "logs"]).unwrap().command == Command::Uids);
+
+        let options = parse(&["diff", "good", "bad"]).unwrap();
+        assert!(options.command == Command::Diff);
+        assert!(options.path == Path::new("good"));
+        assert!(options.other_path.unwrap() == Path::new("bad"));
+
+        assert!(parse(&["diff", "good"]).is_err());
+        assert!(parse(&["diff", "good", "bad", "worse"]).is_err());
+
+        let options = parse(&["bursts", "logs"]).unwrap();
+        assert!(options.command == Command::Bursts);
+        assert!(options.window.is_none());
+
+        assert!(parse(&["index", "logs"]).unwrap().command == Command::Index);
+        assert!(parse(&["view", "logs"]).unwrap().command == Command::View);
+
+        let options = parse(&["serve", "--port", "9000", "logs"]).unwrap();
+        assert!(options.command == Command::Serve);
+        assert!(options.port == Some(9000));
+
+        assert!(parse(&["serve", "--port", "99999"]).is_err());
+        assert!(parse(&["report", "logs"]).unwrap().command == Command::Report);
+
+        let options = parse(&["cat", "--merge", "logs"]).unwrap();
+        assert!(options.command == Command::Cat);
+        assert!(options.merge);
+
+        assert!(parse(&["--merge", "logs"]).is_err());
+
+        let options = parse(&["extract", "--src", "UI", "--output", "trimmed", "logs"]).unwrap();
+        assert!(options.command == Command::Extract);
+        assert!(options.output_folder() == Path::new("trimmed"));
+
+        assert!(parse(&["extract", "logs"]).is_err());
+
+        let options = parse(&["cat", "--redact", "--redact-pattern", "patient [A-Z][a-z]+", "logs"]).unwrap();
+        assert!(options.redact);
+        assert!(options.redact_patterns.len() == 1);
+
+        assert!(parse(&["--redact-pattern", "(", "logs"]).is_err());
+        assert!(parse(&["stats", "--redact", "logs"]).is_err());
+        assert!(parse(&["--redact", "--incremental", "logs"]).is_err());
+    }
+
+    #[test]
+    fn filter_options() {
+        let options = parse(&["--min-level", "warning", "--category", "dev,svc", "logs"]).unwrap();
+        assert!(options.path == Path::new("logs"));
+        assert!(!options.filter.is_empty());
+
+        let options = parse(&["query", "--src", "UI,Recon", "--from", "220101 13:01:02", "--to", "220101 13:01:02.865", "logs"]).unwrap();
+        assert!(options.command == Command::Query);
+        assert!(options.filter.to_string() == "min-level= categories= sources=Recon,UI from=220101 13:01:02.000 to=220101 13:01:02.865");
+
+        assert!(parse(&["--from", "2022-01-01", "logs"]).is_err());
+    }
+
+    #[test]
+    fn output_options() {
+        let options = parse(&["--template-id", "--collapse", "src", "logs"]).unwrap();
+        assert!(options.template_id);
+        assert!(options.collapse_src && !options.collapse_pid);
+
+        let options = parse(&["--collapse", "all", "logs"]).unwrap();
+        assert!(options.collapse_src && options.collapse_pid);
+
+        assert!(parse(&["--collapse", "uid", "logs"]).is_err());
+
+        let options = parse(&["--follow", "--split", "logs"]).unwrap();
+        assert!(options.follow && options.split_output);
+
+        assert!(parse(&["stats", "--follow", "logs"]).is_err());
+
+        assert!(parse(&["logs"]).unwrap().color == ColorMode::Auto);
+        assert!(parse(&["--color", "never", "logs"]).unwrap().color == ColorMode::Never);
+        assert!(parse(&["--color", "red", "logs"]).is_err());
+
+        let options = parse(&["logs"]).unwrap();
+        assert!(options.output_folder() == Path::new("logs"));
+        let options = parse(&[Path::new("export").join("logs.zip").to_str().unwrap()]).unwrap();
+        assert!(options.output_folder() == Path::new("export"));
+        let options = parse(&["--output", "parsed", "logs.tar.gz"]).unwrap();
+        assert!(options.output_folder() == Path::new("parsed"));
+
+        assert!(parse(&["--incremental", "logs.zip"]).is_err());
+
+        assert!(parse(&["logs"]).unwrap().compression == OutputCompression::None);
+        assert!(parse(&["--compress", "zstd", "logs"]).unwrap().compression == OutputCompression::Zstd);
+        assert!(parse(&["--compress", "bzip2", "logs"]).is_err());
+        assert!(parse(&["--compress", "gzip", "--follow", "logs"]).is_err());
+        assert!(parse(&["--collapse", "src", "--follow", "logs"]).is_err());
+        assert!(parse(&["--incremental", "--follow", "logs"]).is_err());
+        assert!(parse(&["--compress", "gzip", "-"]).is_err());
+        assert!(parse(&["--compress", "gzip", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")]).is_err());
+        assert!(parse(&["--pid", "-"]).is_err());
+        assert!(parse(&["--collapse", "src", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")]).is_err());
+        assert!(parse(&[concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"), "1"]).is_ok());
+        assert!(parse(&["--pid", "--collapse", "all", "logs"]).is_ok());
+
+        assert!(parse(&["logs"]).unwrap().threads.is_none());
+        assert!(parse(&["--threads", "8", "logs"]).unwrap().threads == Some(8));
+        assert!(parse(&["--threads", "0", "logs"]).is_err());
+        assert!(parse(&["--max-memory", "256", "logs"]).unwrap().max_memory == Some(256 * 1024 * 1024));
+        assert!(parse(&["--max-memory", "0", "logs"]).is_err());
+        assert!(parse(&["--max-memory", "99999999999999999999", "logs"]).is_err());
+
+        let options = parse(&["--incremental", "logs"]).unwrap();
+        assert!(options.incremental);
+        assert!(options.output_signature() != parse(&["--incremental", "--pid", "logs"]).unwrap().output_signature());
+        assert!(options.output_signature() != parse(&["--incremental", "other"]).unwrap().output_signature());
+    }
+
+    #[test]
+    fn duration_parsing() {
+        assert!(Options::parse_duration("500") == Ok(500));
+        assert!(Options::parse_duration("500ms") == Ok(500));
+        assert!(Options::parse_duration("30s") == Ok(30 * 1000));
+        assert!(Options::parse_duration("5m") == Ok(5 * 60 * 1000));
+        assert!(Options::parse_duration("1h") == Ok(60 * 60 * 1000));
+        assert!(Options::parse_duration("0s").is_err());
+        assert!(Options::parse_duration("1d").is_err());
+        assert!(Options::parse_duration("s").is_err());
+        assert!(Options::parse_duration("9999999999999999h").is_err());
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(parse(&["--min-level"]).is_err());
+        assert!(parse(&["--min-level", "loud"]).is_err());
+        assert!(parse(&["--category", "dev,foo"]).is_err());
+        assert!(parse(&["--foo"]).is_err());
+        assert!(parse(&["stats", "logs", "2"]).is_err());
+    }
+}
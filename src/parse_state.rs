@@ -0,0 +1,181 @@
+use crate::file_system::{file_size, read_prefix};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const STATE_FILE: &str = ".uihlog_state";
+const SIGNATURE_KEY: &str = "signature";
+const HASH_LENGTH: usize = 4096;    // bytes at the beginning of a log file, to tell a replaced file from an appended one
+
+// the identity of a log file, plus the length of its content already parsed
+struct FileState {
+    size: u64,
+    hash: u64,      // of the first HASH_LENGTH bytes, or of the whole file if it was shorter
+    offset: u64,
+}
+
+// the size and the beginning of a log file, to be compared with the last run
+pub fn identify(filepath: &Path) -> io::Result<(u64, Vec<u8>)> {
+    let size = file_size(filepath)?;
+    let prefix = read_prefix(filepath, HASH_LENGTH)?;
+    Ok((size, prefix))
+}
+
+// the hash of the prefix given by identify(), e.g. to tell whether a file was replaced by another one of the same size
+pub fn hash_prefix(prefix: &[u8]) -> u64 {
+    hash_bytes(&prefix[..prefix.len().min(HASH_LENGTH)])
+}
+
+// FNV-1a
+fn hash_bytes(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+// kept in the output folder between runs, so that only the new files and the appended data are parsed
+// Example:
+//   signature	pid=false template-id=false collapse-src=false collapse-pid=false fix-skew=false min-level= categories=
+//   1.uihlog	22677	12638187200555641636	22677
+pub struct ParseState {
+    signature: String,
+    files: BTreeMap<String, FileState>,     // file name => state
+}
+
+impl ParseState {
+    pub fn new(signature: &str) -> Self {
+        ParseState {
+            signature: signature.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    // start over if there is no state of the same signature
+    pub fn load(folder: &Path, signature: &str) -> Self {
+        match fs::read_to_string(folder.join(STATE_FILE)) {
+            Ok(content) => match Self::parse(&content) {
+                Some(state) if state.signature == signature => state,
+                Some(_) => {
+                    eprintln!("output options changed since the last run, parse from scratch");
+                    Self::new(signature)
+                },
+                None => {
+                    eprintln!("invalid state file, parse from scratch");
+                    Self::new(signature)
+                },
+            },
+            Err(_) => Self::new(signature),
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut lines = content.lines();
+        let signature = lines.next()?.strip_prefix(SIGNATURE_KEY)?.strip_prefix('\t')?;
+        let mut state = Self::new(signature);
+        for line in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return None;
+            }
+            state.files.insert(fields[0].to_string(), FileState {
+                size: fields[1].parse().ok()?,
+                hash: fields[2].parse().ok()?,
+                offset: fields[3].parse().ok()?,
+            });
+        }
+        Some(state)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{}\t{}\n", SIGNATURE_KEY, self.signature);
+        for (name, file) in &self.files {
+            text.push_str(&format!("{}\t{}\t{}\t{}\n", name, file.size, file.hash, file.offset));
+        }
+        text
+    }
+
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        fs::write(folder.join(STATE_FILE), self.to_text())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    // the offset to continue parsing from, or None if the file is not the one parsed last time
+    pub fn offset(&self, name: &str, size: u64, prefix: &[u8]) -> Option<u64> {
+        match self.files.get(name) {
+            Some(file) => {
+                let hashed = (file.size as usize).min(HASH_LENGTH);
+                if size < file.offset || prefix.len() < hashed || hash_bytes(&prefix[..hashed]) != file.hash {
+                    return None;
+                }
+                Some(file.offset)
+            },
+            None => Some(0),
+        }
+    }
+
+    pub fn update(&mut self, name: &str, size: u64, prefix: &[u8], offset: u64) {
+        let hashed = (size as usize).min(prefix.len());
+        self.files.insert(name.to_string(), FileState { size, hash: hash_bytes(&prefix[..hashed]), offset });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut state = ParseState::new("SIG");
+        state.update("1.uihlog", 6, b"HEADER", 4);
+
+        let state = ParseState::parse(&state.to_text()).unwrap();
+        assert!(state.signature == "SIG");
+        assert!(state.offset("1.uihlog", 8, b"HEADER++") == Some(4));
+        assert!(state.offset("1.uihlog", 8, b"REPLACED").is_none());
+        assert!(state.offset("1.uihlog", 3, b"HEA").is_none());
+        assert!(state.offset("2.uihlog", 8, b"NEW FILE") == Some(0));
+
+        assert!(ParseState::parse("").is_none());
+        assert!(ParseState::parse("signature\tSIG\n1.uihlog\t100\n").is_none());
+    }
+
+    #[test]
+    fn compressed_file() {
+        use crate::file_system::read_from;
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let folder = std::env::temp_dir().join(format!("uihlog_state_gz_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"HEADER RECORDS").unwrap();
+        fs::write(folder.join("1.uihlog.gz"), gz.finish().unwrap()).unwrap();
+
+        // the entry inside the compressed file, as listed by SortedFileList
+        let entry = folder.join("1.uihlog.gz").join("1.uihlog");
+        let identity = identify(&entry);
+        let rest = read_from(&entry, 7);
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(identity.unwrap() == (14, b"HEADER RECORDS".to_vec()));
+        assert!(rest.unwrap() == b"RECORDS");
+    }
+
+    #[test]
+    fn hashing() {
+        assert!(hash_bytes(b"foo") == hash_bytes(b"foo"));
+        assert!(hash_bytes(b"foo") != hash_bytes(b"bar"));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const FINAL_LINE_COUNT: usize = 5;      // lines kept for each PID before it disappeared
+// a PID is taken as reused by a new process only if it has been silent for this long, and for longer than
+// REUSE_GAP_FACTOR times its longest silence so far, since the processes of a source may run at the same time
+const MIN_REUSE_GAP: i64 = 60 * 1000;   // in milliseconds
+const REUSE_GAP_FACTOR: i64 = 3;
+
+struct Process {
+    pid: String,
+    count: usize,
+    first_ts: i64,
+    last_ts: i64,
+    max_gap: i64,       // the longest silence between two records, in milliseconds
+    final_lines: VecDeque<String>,
+}
+
+#[derive(Default)]
+struct SourceProcesses {
+    processes: Vec<Process>,
+    latest: HashMap<String, usize>,     // pid => the latest process with it
+    last_started: Option<i64>,          // the first timestamp of the latest started process
+}
+
+// track every PID a source ran under, so that the restarts of the source become visible
+pub struct ProcessLifecycle {
+    tz: Option<FixedOffset>,
+    sources: BTreeMap<String, SourceProcesses>,
+}
+
+impl ProcessLifecycle {
+    pub fn new() -> Self {
+        ProcessLifecycle {
+            tz: None,
+            sources: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        let ts = match line.timestamp() {
+            Some(ts) => ts,
+            None => return,
+        };
+        self.tz.get_or_insert(line.tz);
+
+        let source = self.sources.entry(line.src.clone()).or_default();
+
+        // a PID reused by the OS after another process of the source has started is a new process,
+        // if the PID has been silent for long enough to tell a process which ended
+        let index = match source.latest.get(&line.pid) {
+            Some(&index) if !Self::is_reused(&source.processes[index], source.last_started, ts) => index,
+            _ => {
+                source.processes.push(Process {
+                    pid: line.pid.clone(),
+                    count: 0,
+                    first_ts: ts,
+                    last_ts: ts,
+                    max_gap: 0,
+                    final_lines: VecDeque::with_capacity(FINAL_LINE_COUNT),
+                });
+                source.latest.insert(line.pid.clone(), source.processes.len() - 1);
+                source.last_started = Some(source.last_started.map_or(ts, |started| started.max(ts)));
+                source.processes.len() - 1
+            },
+        };
+        let process = &mut source.processes[index];
+
+        process.count += 1;
+        process.max_gap = process.max_gap.max(ts - process.last_ts);
+        process.first_ts = process.first_ts.min(ts);
+        process.last_ts = process.last_ts.max(ts);
+        if process.final_lines.len() == FINAL_LINE_COUNT {
+            process.final_lines.pop_front();
+        }
+        process.final_lines.push_back(line.content.clone());
+    }
+
+    fn is_reused(process: &Process, last_started: Option<i64>, ts: i64) -> bool {
+        let gap = ts - process.last_ts;
+        last_started.is_some_and(|started| started > process.last_ts)
+            && gap > MIN_REUSE_GAP && gap > process.max_gap.saturating_mul(REUSE_GAP_FACTOR)
+    }
+
+    // in the order of the first appearance
+    fn processes<'a>(&self, source: &'a SourceProcesses) -> Vec<&'a Process> {
+        let mut processes: Vec<&Process> = source.processes.iter().collect();
+        processes.sort_by(|a, b| a.first_ts.cmp(&b.first_ts).then(a.pid.cmp(&b.pid)));
+        processes
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+
+        writeln!(w, "== processes per source ==")?;
+        for (src, source) in &self.sources {
+            let processes = self.processes(source);
+            writeln!(w, "{}: {} processes, {} restarts", src, processes.len(), processes.len() - 1)?;
+
+            let mut last_ts: Option<i64> = None;
+            for (i, process) in processes.iter().enumerate() {
+                let started = match last_ts {
+                    Some(last_ts) => format!(", started {} ms after the previous one", process.first_ts - last_ts),
+                    None => String::new(),
+                };
+                writeln!(w, "    pid {}: {} - {}, {} records{}", process.pid,
+                    format_timestamp(process.first_ts, &tz), format_timestamp(process.last_ts, &tz), process.count, started)?;
+
+                // the last process of the source might be still running
+                if i + 1 < processes.len() {
+                    for line in &process.final_lines {
+                        write!(w, "        {}", line)?;
+                    }
+                }
+                last_ts = Some(last_ts.map_or(process.last_ts, |ts| ts.max(process.last_ts)));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn track_processes(folder: &Path, filter: &LogFilter, pool: &WorkerPool) -> io::Result<()> {
+    let mut lifecycle = ProcessLifecycle::new();
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            lifecycle.add(line);
+        }
+        Ok(())
+    })?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    lifecycle.report(&mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts: i64, pid: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_pid(pid)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut lifecycle = ProcessLifecycle::new();
+        for i in 0..10 {
+            lifecycle.add(&line(i * 1000, "2", &format!("LINE{}", i)));
+        }
+        lifecycle.add(&line(12000, "1", "RESTARTED"));
+
+        let processes = lifecycle.processes(&lifecycle.sources["SRC"]);
+        assert!(processes.len() == 2);
+        assert!(processes[0].pid == "2" && processes[0].count == 10 && processes[0].last_ts == 9000);
+        assert!(processes[0].final_lines.len() == FINAL_LINE_COUNT);
+        assert!(processes[0].final_lines.back().unwrap() == "LINE9\n");
+
+        let mut report: Vec<u8> = Vec::new();
+        lifecycle.report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("SRC: 2 processes, 1 restarts"));
+        assert!(report.contains("        LINE5\n        LINE6\n        LINE7\n        LINE8\n        LINE9\n"));
+        assert!(report.contains("pid 1: 700101 00:00:12.000 - 700101 00:00:12.000, 1 records, started 3000 ms after the previous one"));
+        assert!(!report.contains("        RESTARTED"));
+    }
+
+    #[test]
+    fn reused_pids() {
+        let mut lifecycle = ProcessLifecycle::new();
+        lifecycle.add(&line(1000, "1", "STARTED"));
+        lifecycle.add(&line(2000, "1", "CRASHED"));
+        lifecycle.add(&line(3000, "2", "STARTED"));
+        lifecycle.add(&line(4000, "2", "CRASHED"));
+        lifecycle.add(&line(5000 + MIN_REUSE_GAP, "1", "STARTED"));
+        lifecycle.add(&line(6000 + MIN_REUSE_GAP, "1", "RUNNING"));
+
+        let processes = lifecycle.processes(&lifecycle.sources["SRC"]);
+        assert!(processes.iter().map(|process| process.pid.as_str()).eq(["1", "2", "1"]));
+        assert!(processes[0].last_ts == 2000 && processes[2].first_ts == 5000 + MIN_REUSE_GAP && processes[2].count == 2);
+    }
+
+    #[test]
+    fn interleaved_pids() {
+        // two processes of the source running at the same time, e.g. on different hosts
+        let mut lifecycle = ProcessLifecycle::new();
+        for i in 0..10 {
+            lifecycle.add(&line(i * 1000, "1", "RUNNING"));
+            lifecycle.add(&line(i * 1000 + 500, "2", "RUNNING"));
+        }
+        // silent for a while, but not much longer than before
+        lifecycle.add(&line(9000 + MIN_REUSE_GAP / 2, "2", "RUNNING"));
+        lifecycle.add(&line(9000 + MIN_REUSE_GAP, "1", "RUNNING"));
+        lifecycle.add(&line(9000 + MIN_REUSE_GAP * 2, "2", "RUNNING"));
+
+        let processes = lifecycle.processes(&lifecycle.sources["SRC"]);
+        assert!(processes.len() == 2);
+        assert!(processes[0].count == 11 && processes[1].count == 12);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::file_system::get_file_list;
+use crate::log_archive::{is_archive, is_compressed_log, list_entries};
 
 use std::path::{Path, PathBuf};
 
@@ -8,11 +9,24 @@ pub struct SortedFileList {
 }
 
 impl SortedFileList {
+    // an archive is listed like a folder, and the compressed log files in a folder along with the plain ones
     pub fn new(folder: &Path) -> SortedFileList {
-        let files = get_file_list(folder);
+        let files = match is_archive(folder) && folder.is_file() {
+            true => Self::list_archive(folder),
+            false => get_file_list(folder).into_iter()
+                .flat_map(|file| if is_compressed_log(&file) { Self::list_archive(&file) } else { vec![file] })
+                .collect(),
+        };
         Self::do_new(files)
     }
 
+    fn list_archive(archive: &Path) -> Vec<PathBuf> {
+        list_entries(archive).unwrap_or_else(|e| {
+            eprintln!("failed to list the archive {:?}: {}", archive, e);
+            Vec::new()
+        })
+    }
+
     fn do_new(files: Vec<PathBuf>) -> SortedFileList {
         SortedFileList { files: Self::sort_files(files), index:0 }
     }
@@ -24,8 +38,8 @@ impl SortedFileList {
                 if ext != "uihlog" {
                     continue;
                 }
-                if Self::extract_id(&file) == None {
-                    println!("invalid file name, skipped: {:?}", &file);
+                if Self::extract_id(&file).is_none() {
+                    eprintln!("invalid file name, skipped: {:?}", &file);
                     continue;
                 }
                 sorted.push(file);
@@ -40,11 +54,11 @@ impl SortedFileList {
         sorted
     }
 
-    fn extract_id(path: &PathBuf) -> Option<i32> {
+    fn extract_id(path: &Path) -> Option<i32> {
         if let Some(filename) = path.file_stem().and_then(|f|f.to_str()) {
             let id = match filename.find(".") {
                 Some(idx) => &filename[..idx],
-                None => &filename
+                None => filename
             };
             if let Ok(id) = id.parse::<i32>() {
                 return Some(id)
@@ -71,32 +85,37 @@ impl SortedFileList {
 mod tests {
     use super::*;
 
+    // built with the platform separator, so that the tests also work outside of Windows
+    fn path(filename: &str) -> PathBuf {
+        Path::new("c:").join(filename)
+    }
+
     #[test]
     fn id_extraction() {
-        let path = PathBuf::from(r"c:\test\1.uihlog");
+        let path = Path::new("c:").join("test").join("1.uihlog");
         assert_eq!(SortedFileList::extract_id(&path), Some(1));
 
-        let path = PathBuf::from(r"c:\test\1.svc.uihlog");
+        let path = Path::new("c:").join("test").join("1.svc.uihlog");
         assert_eq!(SortedFileList::extract_id(&path), Some(1));
     }
 
     #[test]
     fn filenames_with_same_length() {
-        let files = vec![PathBuf::from(r"c:\2.uihlog"), PathBuf::from(r"c:\1.uihlog")];
+        let files = vec![path("2.uihlog"), path("1.uihlog")];
         let mut sfl = SortedFileList::do_new(files);
         assert!(sfl.count() == 2);
-        assert!(sfl.next().unwrap().to_str().unwrap() == r"c:\1.uihlog");
-        assert!(sfl.next().unwrap().to_str().unwrap() == r"c:\2.uihlog");
+        assert!(sfl.next().unwrap() == path("1.uihlog"));
+        assert!(sfl.next().unwrap() == path("2.uihlog"));
         assert!(sfl.next().is_none());
     }
 
     #[test]
     fn filenames_with_different_length() {
-        let files = vec![PathBuf::from(r"c:\10.uihlog"), PathBuf::from(r"c:\2.uihlog")];
+        let files = vec![path("10.uihlog"), path("2.uihlog")];
         let mut sfl = SortedFileList::do_new(files);
         assert!(sfl.count() == 2);
-        assert!(sfl.next().unwrap().to_str().unwrap() == r"c:\2.uihlog");
-        assert!(sfl.next().unwrap().to_str().unwrap() == r"c:\10.uihlog");
+        assert!(sfl.next().unwrap() == path("2.uihlog"));
+        assert!(sfl.next().unwrap() == path("10.uihlog"));
         assert!(sfl.next().is_none());
     }
 
@@ -110,7 +129,7 @@ mod tests {
 
     #[test]
     fn invalid_files() {
-        let files= vec![PathBuf::from(r"c:\thumbs.db"), PathBuf::from(".cargo-lock"), PathBuf::from(r"c:\invalid.uihlog")];
+        let files= vec![path("thumbs.db"), PathBuf::from(".cargo-lock"), path("invalid.uihlog")];
         let mut sfl = SortedFileList::do_new(files);
         assert!(sfl.count() == 0);
         assert!(sfl.next().is_none());
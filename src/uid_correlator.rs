@@ -0,0 +1,219 @@
+use crate::buffered_output::FileWriter;
+use crate::file_system::{read_bytes, RealFileWriter};
+use crate::folder_parser::parse_folder_with;
+use crate::log_filter::LogFilter;
+use crate::log_parser::{format_timestamp, to_file_stem, LogLine};
+use crate::worker_pool::WorkerPool;
+
+use chrono::FixedOffset;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+const UID_FILE_PREFIX: &str = "uid_";     // to tell the uid outputs from the source outputs
+const SPILL_THRESHOLD: usize = 16 * 1024 * 1024;     // of the record contents kept before they're written out
+
+struct Operation {
+    first_ts: Option<i64>,
+    last_ts: Option<i64>,
+    sources: BTreeSet<String>,
+    pids: BTreeSet<String>,
+    count: usize,
+    lines: Vec<(i64, usize)>,       // (timestamp, content length) in the order written, released once sorted
+    pending: String,                // the contents not written yet
+    written: bool,
+}
+
+// group the records by uid across all the sources and PIDs, to follow one logical operation
+// the contents go to one file per uid as they come, and each file is put in time order at the end,
+// so that only one uid's records are held at a time
+pub struct UidCorrelator {
+    tz: Option<FixedOffset>,
+    uids: BTreeMap<String, Operation>,
+    pending: usize,                 // the length of all the pending contents
+}
+
+impl UidCorrelator {
+    pub fn new() -> Self {
+        UidCorrelator {
+            tz: None,
+            uids: BTreeMap::new(),
+            pending: 0,
+        }
+    }
+
+    pub fn add(&mut self, line: &LogLine) {
+        if line.uid.is_empty() {
+            return;
+        }
+        self.tz.get_or_insert(line.tz);
+
+        let operation = self.uids.entry(line.uid.clone()).or_insert_with(|| Operation {
+            first_ts: None,
+            last_ts: None,
+            sources: BTreeSet::new(),
+            pids: BTreeSet::new(),
+            count: 0,
+            lines: Vec::new(),
+            pending: String::new(),
+            written: false,
+        });
+        operation.sources.insert(line.src.clone());
+        operation.pids.insert(line.pid.clone());
+        operation.count += 1;
+
+        // records without a valid timestamp are kept at the beginning
+        let ts = line.timestamp().unwrap_or(i64::MIN);
+        if let Some(ts) = line.timestamp() {
+            operation.first_ts = Some(operation.first_ts.map_or(ts, |first| first.min(ts)));
+            operation.last_ts = Some(operation.last_ts.map_or(ts, |last| last.max(ts)));
+        }
+        operation.lines.push((ts, line.content.len()));
+        operation.pending.push_str(&line.content);
+        self.pending += line.content.len();
+    }
+
+    fn filepath(folder: &Path, uid: &str) -> PathBuf {
+        folder.join(UID_FILE_PREFIX.to_string() + &to_file_stem(uid) + ".txt")
+    }
+
+    // write out the pending contents once there are too many of them
+    pub fn spill<T: FileWriter>(&mut self, folder: &Path, writer: &T) -> io::Result<()> {
+        if self.pending < SPILL_THRESHOLD {
+            return Ok(());
+        }
+        self.write_pending(folder, writer)
+    }
+
+    fn write_pending<T: FileWriter>(&mut self, folder: &Path, writer: &T) -> io::Result<()> {
+        for (uid, operation) in self.uids.iter_mut().filter(|(_, operation)| !operation.pending.is_empty()) {
+            writer.write(&Self::filepath(folder, uid), mem::take(&mut operation.pending).as_bytes(), operation.written)?;
+            operation.written = true;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+
+    // one time-ordered file per uid, read back and sorted one uid at a time if its records came out of order
+    pub fn write<T, R>(&mut self, folder: &Path, writer: &T, read: R) -> io::Result<()>
+    where T: FileWriter, R: Fn(&Path) -> io::Result<Vec<u8>>,
+    {
+        self.write_pending(folder, writer)?;
+        for (uid, operation) in self.uids.iter_mut() {
+            let lines = mem::take(&mut operation.lines);
+            if lines.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+                continue;
+            }
+            let filepath = Self::filepath(folder, uid);
+            let content = read(&filepath)?;
+            let mut records: Vec<(i64, &[u8])> = Vec::with_capacity(lines.len());
+            let mut start = 0;
+            for (ts, length) in lines {
+                let end = (start + length).min(content.len());
+                records.push((ts, &content[start..end]));
+                start = end;
+            }
+            records.sort_by_key(|(ts, _)| *ts);     // stable, so the file order is kept for the same timestamp
+            let mut sorted = Vec::with_capacity(content.len());
+            for (_, record) in records {
+                sorted.extend_from_slice(record);
+            }
+            writer.write(&filepath, &sorted, false)?;
+        }
+        Ok(())
+    }
+
+    pub fn report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let tz = self.tz.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        let format_ts = |ts: Option<i64>| match ts {
+            Some(ts) => format_timestamp(ts, &tz),
+            None => "INVALID_TS".to_string(),
+        };
+
+        writeln!(w, "== sources per uid ==")?;
+        for (uid, operation) in &self.uids {
+            writeln!(w, "{}: {} records, from {} to {}",
+                uid, operation.count, format_ts(operation.first_ts), format_ts(operation.last_ts))?;
+            writeln!(w, "    sources: {}", operation.sources.iter().cloned().collect::<Vec<String>>().join(", "))?;
+            writeln!(w, "    pids: {}", operation.pids.iter().cloned().collect::<Vec<String>>().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn correlate_folder(folder: &Path, output: &Path, filter: &LogFilter, pool: &WorkerPool) -> io::Result<()> {
+    fs::create_dir_all(output)?;
+    let writer = RealFileWriter::new();
+    let mut correlator = UidCorrelator::new();
+    parse_folder_with(folder, pool, |lines| {
+        for line in lines.iter().filter(|line| filter.accept(line)) {
+            correlator.add(line);
+        }
+        correlator.spill(output, &writer)
+    })?;
+    correlator.write(output, &writer, read_bytes)?;
+
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    correlator.report(&mut w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    struct MapWriter {
+        files: RefCell<HashMap<PathBuf, String>>,
+    }
+
+    impl FileWriter for MapWriter {
+        fn write(&self, filepath: &Path, content: &[u8], append: bool) -> io::Result<()> {
+            let mut files = self.files.borrow_mut();
+            let file = files.entry(filepath.to_path_buf()).or_default();
+            if !append {
+                file.clear();
+            }
+            file.push_str(std::str::from_utf8(content).unwrap());
+            Ok(())
+        }
+    }
+
+    fn line(ts: i64, src: &str, uid: &str, desc: &str) -> LogLine {
+        LogLine::test(desc).with_ts(ts).with_src(src).with_uid(uid)
+    }
+
+    #[test]
+    fn it_works() {
+        let mut correlator = UidCorrelator::new();
+        correlator.add(&line(2000, "SRC1", "0X2001", "SECOND"));
+        correlator.add(&line(3000, "SRC1", "0X2002", "OTHER"));
+        correlator.add(&line(1000, "SRC2", "0X2001", "FIRST"));
+        correlator.add(&line(2000, "SRC2", "0X2001", "THIRD"));
+        correlator.add(&line(4000, "SRC2", "", "NO_UID"));
+
+        let writer = MapWriter { files: RefCell::new(HashMap::new()) };
+        let read = |filepath: &Path| Ok(writer.files.borrow()[filepath].clone().into_bytes());
+        // written as they came, then sorted
+        correlator.write_pending(Path::new("FOO"), &writer).unwrap();
+        correlator.add(&line(1500, "SRC1", "0X2001", "BETWEEN"));
+        correlator.add(&line(5000, "SRC1", "0X2002", "LAST"));
+        correlator.write(Path::new("FOO"), &writer, read).unwrap();
+
+        let files = writer.files.borrow();
+        assert!(files.len() == 2);
+        assert!(correlator.uids.values().all(|operation| operation.lines.is_empty() && operation.pending.is_empty()));
+        assert!(files[&Path::new("FOO").join("uid_0X2001.txt")] == "FIRST\nBETWEEN\nSECOND\nTHIRD\n");
+        assert!(files[&Path::new("FOO").join("uid_0X2002.txt")] == "OTHER\nLAST\n");
+
+        let mut report: Vec<u8> = Vec::new();
+        correlator.report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("0X2001: 4 records, from 700101 00:00:01.000 to 700101 00:00:02.000\n    sources: SRC1, SRC2\n"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+// the memory reserved by the jobs for their data not handed over yet, by default
+pub const DEFAULT_MEMORY_LIMIT: usize = 512 * 1024 * 1024;
+const FALLBACK_THREAD_COUNT: usize = 2;
+
+struct Budget {
+    in_flight: usize,   // in bytes
+    next: usize,        // the index of the next job to hand over
+    stopped: bool,      // the handler has failed
+}
+
+struct Shared {
+    budget: Mutex<Budget>,
+    changed: Condvar,
+    limit: usize,
+}
+
+// the job being worked on, which reserves the memory for its data until it's handed over
+pub struct Ticket<'a> {
+    index: usize,
+    reserved: usize,
+    shared: &'a Shared,
+}
+
+impl Ticket<'_> {
+    // wait until the data fits in the limit, except for the next job to hand over, so that the pool always goes on
+    pub fn reserve(&mut self, size: usize) {
+        let mut budget = self.shared.budget.lock().unwrap();
+        while budget.in_flight > 0 && budget.in_flight + size > self.shared.limit && budget.next != self.index && !budget.stopped {
+            budget = self.shared.changed.wait(budget).unwrap();
+        }
+        budget.in_flight += size;
+        self.reserved += size;
+    }
+}
+
+// read and parse the files with worker threads, and hand over the results in the order of the files
+pub struct WorkerPool {
+    threads: usize,
+    limit: usize,
+}
+
+impl WorkerPool {
+    // the available cores if the thread count isn't given
+    pub fn new(threads: Option<usize>) -> Self {
+        WorkerPool {
+            threads: threads.unwrap_or_else(|| thread::available_parallelism().map(|count| count.get()).unwrap_or(FALLBACK_THREAD_COUNT)),
+            limit: DEFAULT_MEMORY_LIMIT,
+        }
+    }
+
+    // in bytes
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    // the jobs are taken in order, so the next one to hand over is always taken before the ones waiting for memory
+    pub fn run<J, R, W, H>(&self, jobs: Vec<J>, work: W, mut handle: H) -> io::Result<()>
+    where
+        J: Send,
+        R: Send,
+        W: Fn(J, &mut Ticket) -> R + Sync,
+        H: FnMut(R) -> io::Result<()>,
+    {
+        let threads = cmp::min(self.threads, jobs.len());
+        let jobs = Mutex::new(jobs.into_iter().enumerate());
+        let shared = Shared {
+            budget: Mutex::new(Budget { in_flight: 0, next: 0, stopped: false }),
+            changed: Condvar::new(),
+            limit: self.limit,
+        };
+
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel::<(usize, R, usize)>();
+            for _ in 0..threads {
+                let (tx, jobs, shared, work) = (tx.clone(), &jobs, &shared, &work);
+                scope.spawn(move || loop {
+                    if shared.budget.lock().unwrap().stopped {
+                        break;
+                    }
+                    let (index, job) = match jobs.lock().unwrap().next() {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    let mut ticket = Ticket { index, reserved: 0, shared };
+                    let result = work(job, &mut ticket);
+                    if tx.send((index, result, ticket.reserved)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            // the results done ahead of the next one wait here, within the memory limit
+            let mut pending: BTreeMap<usize, (R, usize)> = BTreeMap::new();
+            let mut next = 0;
+            for (index, result, reserved) in rx.iter() {
+                pending.insert(index, (result, reserved));
+                while let Some((result, reserved)) = pending.remove(&next) {
+                    let handled = handle(result);
+                    next += 1;
+                    let mut budget = shared.budget.lock().unwrap();
+                    budget.in_flight -= reserved;
+                    budget.next = next;
+                    budget.stopped = handled.is_err();
+                    shared.changed.notify_all();
+                    handled?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn it_works() {
+        let pool = WorkerPool::new(Some(4));
+        let mut results = Vec::new();
+        pool.run((0..20).collect(), |job: u64, ticket| {
+            ticket.reserve(10);
+            thread::sleep(Duration::from_millis((20 - job) % 7));
+            job * 2
+        }, |result| {
+            results.push(result);
+            Ok(())
+        }).unwrap();
+        assert!(results == (0..20).map(|job| job * 2).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn memory_limit() {
+        let mut pool = WorkerPool::new(Some(4));
+        pool.set_memory_limit(25);
+        let max_in_flight = AtomicUsize::new(0);
+        let mut count = 0;
+        pool.run((0..20).collect(), |job: u64, ticket| {
+            ticket.reserve(10);
+            let in_flight = ticket.shared.budget.lock().unwrap().in_flight;
+            max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(job % 3));
+        }, |_| {
+            count += 1;
+            Ok(())
+        }).unwrap();
+        assert!(count == 20);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 25 + 10);   // the next job to hand over may go beyond the limit
+    }
+
+    #[test]
+    fn failed_handler() {
+        let mut pool = WorkerPool::new(Some(3));
+        pool.set_memory_limit(25);
+        let mut handled = Vec::new();
+        let result = pool.run((0..20).collect(), |job: u64, ticket| {
+            ticket.reserve(10);
+            job
+        }, |job| {
+            if job == 3 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+            }
+            handled.push(job);
+            Ok(())
+        });
+        assert!(result.unwrap_err().kind() == io::ErrorKind::BrokenPipe);
+        assert!(handled == vec![0, 1, 2]);
+    }
+}